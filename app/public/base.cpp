@@ -3,6 +3,7 @@
 #include <string>
 #include <sstream>
 #include <optional>
+#include <cstdio>
 
 
 struct Piece {
@@ -21,8 +22,20 @@ struct Move {
     Position to; // to: cellule d'arrivée
 };
 
+// Une cellule s'écrit avec un chiffre par coordonnée, ou deux au-delà de 9
+std::string cell(const Position& position) {
+    if (position.row < 10 && position.column < 10) {
+        return std::to_string(position.row) + std::to_string(position.column);
+    }
+    char buffer[5];
+    std::snprintf(buffer, sizeof(buffer), "%02d%02d", position.row, position.column);
+    return buffer;
+}
+
 // Fonction pour trouver les coups à jouer
-std::vector<Move> findMove(const std::vector<std::vector<std::optional<Piece>>>& board, char playerColor) {
+// variant: "international", "frisian", "canadian" sur 12×12, ou "english", "russian",
+// "brazilian" et "turkish" sur 8×8
+std::vector<Move> findMove(const std::vector<std::vector<std::optional<Piece>>>& board, char playerColor, const std::string& variant) {
     
     // TODO: Implémentez ici la logique pour trouver les coups à jouer et les retourner
     // Les coups doivent être retournés sous forme d'une liste d'objets Move,
@@ -40,17 +53,31 @@ std::vector<Move> findMove(const std::vector<std::vector<std::optional<Piece>>>&
 }
 
 int main() {
-    std::vector<std::vector<std::optional<Piece>>> board(10, std::vector<std::optional<Piece>>(10));
     char playerColor;
 
     // Lecture de la couleur du joueur depuis la console
     std::cin >> playerColor;
     std::cin.ignore(); // Ignore the newline character after reading playerColor
 
-    // Parsage du plateau de jeu depuis la console
-    for (int r = 0; r < 10; r++) {
-        std::string line;
+    // Lecture de la variante, donnée seulement si elle n'est pas internationale
+    std::string variant = "international";
+    std::string line;
+    std::getline(std::cin, line);
+    if (line.rfind("variant ", 0) == 0) {
+        variant = line.substr(8);
         std::getline(std::cin, line);
+    }
+
+    // La taille du plateau est le nombre de cellules d'une ligne
+    int size = 1;
+    for (char ch : line) {
+        if (ch == ',') size++;
+    }
+    std::vector<std::vector<std::optional<Piece>>> board(size, std::vector<std::optional<Piece>>(size));
+
+    // Parsage du plateau de jeu depuis la console
+    for (int r = 0; r < size; r++) {
+        if (r > 0) std::getline(std::cin, line);
         std::stringstream ss(line);
         std::string pieceCode;
         int c = 0;
@@ -65,8 +92,40 @@ int main() {
         }
     }
 
+    // Lignes facultatives qui suivent le plateau :
+    // "time <le vôtre> <celui de l'adversaire>" : temps restants en millisecondes
+    // "book <coups> <poids>" : une ligne par suite de coups du livre d'ouverture
+    // "draw" : l'adversaire propose la nulle
+    std::optional<std::pair<long, long>> timeLeft;
+    std::vector<std::pair<std::string, int>> book;
+    bool drawOffered = false;
+    while (std::getline(std::cin, line)) {
+        std::stringstream words(line);
+        std::string word;
+        words >> word;
+        if (word == "time") {
+            long own, opponent;
+            words >> own >> opponent;
+            timeLeft = std::make_pair(own, opponent);
+        } else if (word == "book") {
+            std::string bookMoves;
+            int weight;
+            words >> bookMoves >> weight;
+            book.emplace_back(bookMoves, weight);
+        } else if (word == "draw") {
+            drawOffered = true;
+        }
+    }
+
+    // Pour accepter la nulle, affichez "draw" au lieu de vos coups. Jouer un
+    // coup la refuse.
+    // if (drawOffered) {
+    //     std::cout << "draw" << std::endl;
+    //     return 0;
+    // }
+
     // Appel de la fonction findMove pour trouver les coups à jouer
-    auto moves = findMove(board, playerColor);
+    auto moves = findMove(board, playerColor, variant);
 
     if (moves.empty()) {
         std::cerr << "No moves were returned." << std::endl;
@@ -75,9 +134,7 @@ int main() {
 
     // Envoi des coups trouvés à la console
     for (const auto& pos : moves) {
-        std::cout 
-            << pos.from.row << pos.from.column << "," 
-            << pos.to.row << pos.to.column << ";";
+        std::cout << cell(pos.from) << "," << cell(pos.to) << ";";
     }
     std::cout << std::endl;
 
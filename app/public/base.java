@@ -1,3 +1,4 @@
+import java.util.ArrayList;
 import java.util.List;
 import java.util.Scanner;
 
@@ -12,8 +13,18 @@ public class base {
     // from: position de départ, to: position d'arrivée
     private record Move(Position from, Position to) {}
 
+    // Une cellule s'écrit avec un chiffre par coordonnée, ou deux au-delà de 9
+    private static String cell(Position position) {
+        if (position.row() < 10 && position.column() < 10) {
+            return position.row() + "" + position.column();
+        }
+        return String.format("%02d%02d", position.row(), position.column());
+    }
+
     // Fonction pour trouver les coups à jouer
-    private static List<Move> findMove(Piece[][] board, char playerColor) {
+    // variant: "international", "frisian", "canadian" sur 12×12, ou "english", "russian",
+    // "brazilian" et "turkish" sur 8×8
+    private static List<Move> findMove(Piece[][] board, char playerColor, String variant) {
 
         // TODO: Implémentez ici la logique pour trouver les coups à jouer et les retourner
         // Les coups doivent être retournés sous forme d'une liste d'objets Move,
@@ -27,15 +38,28 @@ public class base {
 
     public static void main(String[] args) {
         Scanner scanner = new Scanner(System.in);
-        Piece[][] board = new Piece[10][10];
 
         // Lecture de la couleur du joueur depuis la console
         char playerColor = scanner.nextLine().charAt(0);
 
+        // Lecture de la variante, donnée seulement si elle n'est pas internationale
+        String variant = "international";
+        String line = scanner.nextLine();
+        if (line.startsWith("variant ")) {
+            variant = line.substring(8);
+            line = scanner.nextLine();
+        }
+
+        // La taille du plateau est le nombre de cellules d'une ligne
+        int size = line.split(",", -1).length;
+        Piece[][] board = new Piece[size][size];
+
         // Parsage du plateau de jeu depuis la console
-        for (int r = 0; r < 10; r++) {
-            String line = scanner.nextLine();
-            String[] row = line.split(",");
+        for (int r = 0; r < size; r++) {
+            if (r > 0) {
+                line = scanner.nextLine();
+            }
+            String[] row = line.split(",", -1);
             Piece[] pieceRow = new Piece[row.length];
             for (int c = 0; c < row.length; c++) {
                 String pieceCode = row[c];
@@ -49,10 +73,34 @@ public class base {
             board[r] = pieceRow;
         }
 
+        // Lignes facultatives qui suivent le plateau :
+        // "time <le vôtre> <celui de l'adversaire>" : temps restants en millisecondes
+        // "book <coups> <poids>" : une ligne par suite de coups du livre d'ouverture
+        // "draw" : l'adversaire propose la nulle
+        long[] timeLeft = null;
+        List<String[]> book = new ArrayList<>();
+        boolean drawOffered = false;
+        while (scanner.hasNextLine()) {
+            String[] words = scanner.nextLine().trim().split(" ");
+            switch (words[0]) {
+                case "time" -> timeLeft = new long[] {Long.parseLong(words[1]), Long.parseLong(words[2])};
+                case "book" -> book.add(new String[] {words[1], words[2]});
+                case "draw" -> drawOffered = true;
+                default -> {}
+            }
+        }
+
         scanner.close();
 
+        // Pour accepter la nulle, affichez "draw" au lieu de vos coups. Jouer un
+        // coup la refuse.
+        // if (drawOffered) {
+        //     System.out.println("draw");
+        //     return;
+        // }
+
         // Appel de la fonction findMove pour trouver les coups à jouer
-        List<Move> moves = findMove(board, playerColor);
+        List<Move> moves = findMove(board, playerColor, variant);
 
         if (moves == null) {
             return;
@@ -60,10 +108,7 @@ public class base {
 
         // Envoi des coups trouvés à la console
         for (Move move : moves) {
-            System.out.print(
-                move.from.row + "" + move.from.column + "," 
-                + move.to.row + "" + move.to.column + ";"
-            );
+            System.out.print(cell(move.from) + "," + cell(move.to) + ";");
         }
         System.out.println("");
     }
@@ -1,3 +1,6 @@
+import sys
+
+
 class Piece:
     # piece_type: M pour pion, K pour dame
     # piece_color: B pour noir, W pour blanc
@@ -20,18 +23,27 @@ class Move:
         self.end = end
 
     def __str__(self):
-        return f"{self.start.row}{self.start.col},{self.end.row}{self.end.col};"
+        return f"{cell(self.start)},{cell(self.end)};"
 
     def __repr__(self):
         return self.__str__()
 
 
-def find_move(board, player_color):
+# Une cellule s'écrit avec un chiffre par coordonnée, ou deux au-delà de 9
+def cell(position):
+    if position.row < 10 and position.col < 10:
+        return f"{position.row}{position.col}"
+    return f"{position.row:02}{position.col:02}"
+
+
+def find_move(board, player_color, variant):
 
     # TODO: Implémentez ici la logique pour trouver les coups à jouer et les retourner
     # Les coups doivent être retournés sous forme d'une liste d'objets Move,
     # Chaque objet Move représente un coup, avec une cellule de départ et une cellule d'arrivée
     # Les classes Position(row, column) et Move(start, end) sont fournies pour vous
+    # variant: "international", "frisian", "canadian" sur 12×12, ou "english", "russian",
+    # "brazilian" et "turkish" sur 8×8
     
     moves = [Move(Position(6, 1), Position(5, 0))]
 
@@ -39,16 +51,26 @@ def find_move(board, player_color):
 
 
 def main():
-    board = [
-        [None for _ in range(10)] for _ in range(10)
-    ]
-
     # Lecture de la couleur du joueur depuis la console
     player_color = input().strip()[0]
 
-    # Parsage du plateau de jeu depuis la console
-    for r in range(10):
+    # Lecture de la variante, donnée seulement si elle n'est pas internationale
+    variant = "international"
+    line = input().strip()
+    if line.startswith("variant"):
+        variant = line.split()[1]
         line = input().strip()
+
+    # La taille du plateau est le nombre de cellules d'une ligne
+    size = len(line.split(","))
+    board = [
+        [None for _ in range(size)] for _ in range(size)
+    ]
+
+    # Parsage du plateau de jeu depuis la console
+    for r in range(size):
+        if r > 0:
+            line = input().strip()
         row = line.split(",")
         for c, piece_code in enumerate(row):
             if piece_code:
@@ -56,8 +78,32 @@ def main():
             else:
                 board[r][c] = None 
 
+    # Lignes facultatives qui suivent le plateau :
+    # "time <le vôtre> <celui de l'adversaire>" : temps restants en millisecondes
+    # "book <coups> <poids>" : une ligne par suite de coups du livre d'ouverture
+    # "draw" : l'adversaire propose la nulle
+    time_left = None
+    book = []
+    draw_offered = False
+    for line in sys.stdin:
+        words = line.split()
+        if not words:
+            continue
+        if words[0] == "time":
+            time_left = (int(words[1]), int(words[2]))
+        elif words[0] == "book":
+            book.append((words[1], int(words[2])))
+        elif words[0] == "draw":
+            draw_offered = True
+
+    # Pour accepter la nulle, affichez "draw" au lieu de vos coups. Jouer un
+    # coup la refuse.
+    # if draw_offered:
+    #     print("draw")
+    #     return
+
     # Appel de la fonction findMove pour trouver les coups à jouer
-    moves = find_move(board, player_color)
+    moves = find_move(board, player_color, variant)
 
     if not moves:
         raise Exception("No moves were returned.")
@@ -46,39 +46,75 @@ async function apiCall(
   });
 }
 
-export async function login(username: string): Promise<boolean> {
-  return (
-    await apiCall(`login?name=${username}`, {
-      method: "POST",
-    })
-  ).ok;
+/**
+ * Registers or logs into an account, and returns the session token or the
+ * HTTP status code of the failure.
+ */
+export async function login(
+  username: string,
+  password: string
+): Promise<{ token: string } | { status: number }> {
+  const response = await apiCall("login", {
+    method: "POST",
+    body: { name: username, password },
+  });
+  return response.ok ? await response.json() : { status: response.status };
+}
+
+/**
+ * Extracts the user's name from a session token.
+ */
+export function sessionUsername(session: string): string {
+  const payload = session.split(".")[0];
+  return JSON.parse(Buffer.from(payload, "base64url").toString()).sub;
 }
 
+/**
+ * Errors are sent as `{ code, message, details }`, where the details of a
+ * failure of the AI are an `AIError`.
+ */
+async function turnStatus(response: Response): Promise<TurnStatus | AIError> {
+  const body = await response.json();
+  return response.ok ? body : body.details;
+}
+
+/**
+ * Starts a game against the user's submission, replacing the one still
+ * running, if any.
+ */
 export async function createGame(
   session: string,
   isFirstPlayer: boolean
 ): Promise<TurnStatus | AIError> {
-  return await (
-    await apiCall(`game/start?is_first_player=${isFirstPlayer}`, {
+  const start = () =>
+    apiCall(`game/start?is_first_player=${isFirstPlayer}`, {
       session,
       method: "POST",
-    })
-  ).json();
+    });
+
+  let response = await start();
+  if (response.status === 409) {
+    const { details } = await response.json();
+    await apiCall(`game/stop?id=${details.game}`, { session, method: "POST" });
+    response = await start();
+  }
+
+  return await turnStatus(response);
 }
 
 export async function getGameState(
   session: string
 ): Promise<TurnStatus | AIError> {
-  return await (await apiCall("game", { session, method: "GET" })).json();
+  return await turnStatus(await apiCall("game", { session, method: "GET" }));
 }
 
 export async function makeMove(
   moves: MoveSequence,
   session: string
 ): Promise<TurnStatus | AIError> {
-  return await (
+  return await turnStatus(
     await apiCall("game", { session, body: moves, method: "POST" })
-  ).json();
+  );
 }
 
 export async function stopGame(session: string): Promise<void> {
@@ -1,14 +1,27 @@
 export interface GameState {
   board: Board;
   current_player: Player;
+  variant: Variant;
+}
+
+export enum Variant {
+  International = "international",
+  English = "english",
+  Russian = "russian",
+  Brazilian = "brazilian",
+  Frisian = "frisian",
+  Turkish = "turkish",
+  Canadian = "canadian",
 }
 export interface TurnStatus {
+  id: number;
   game: GameState;
   ai_output: string; // Everything printed by the AI on stderr since the start/last move.
+  job: number | null;
 }
 
 export enum AIErrorType {
-  NoSubmission = "noSubmission",
+  EmptySubmission = "emptySubmission",
   InvalidMove = "invalidMove",
   InvalidOutput = "invalidOutput",
 }
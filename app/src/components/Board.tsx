@@ -25,7 +25,7 @@ import { makeMove } from "@/api/api";
 import { ConsoleMessage } from "@/pages";
 
 type BoardProps = {
-  username: string;
+  session: string;
   player: Player;
   gameOngoing: boolean;
   currentTurn: Player | null;
@@ -39,7 +39,7 @@ type BoardProps = {
 };
 
 export default function Board({
-  username,
+  session,
   player,
   gameOngoing,
   currentTurn,
@@ -107,7 +107,7 @@ export default function Board({
             setCurrentTurn(
               player == Player.White ? Player.Black : Player.White
             ); // switch turn
-            makeMove(rotateMove(newMoveSequence, player), username).then(
+            makeMove(rotateMove(newMoveSequence, player), session).then(
               (turnStatus) => {
                 updateGame(turnStatus);
               },
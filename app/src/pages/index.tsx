@@ -17,6 +17,7 @@ import {
   createGame,
   loadSubmission,
   requireSession,
+  sessionUsername,
   stopGame,
   submitCode,
 } from "../api/api";
@@ -34,7 +35,13 @@ export enum ConsoleMessageType {
 }
 export type ConsoleMessage = { msg: string; msgType: ConsoleMessageType };
 
-export default function Home({ username }: { username: string }) {
+export default function Home({
+  username,
+  session,
+}: {
+  username: string;
+  session: string;
+}) {
   const [selectedLang, setLang] = useState(SubmissionLanguage.Java);
   const [file, setFile] = useState("");
   const [gameOngoing, setGameOngoing] = useState(false);
@@ -86,7 +93,7 @@ export default function Home({ username }: { username: string }) {
 
     if ("error" in turnStatus) {
       switch (turnStatus.error) {
-        case AIErrorType.NoSubmission:
+        case AIErrorType.EmptySubmission:
           newConsoleOutput = newConsoleOutput.concat({
             msg: "No submission found",
             msgType: ConsoleMessageType.Error,
@@ -160,12 +167,12 @@ export default function Home({ username }: { username: string }) {
               className="button"
               onClick={() => {
                 if (gameOngoing) {
-                  stopGame(username);
+                  stopGame(session);
                   setGameOngoing(false);
                 } else {
                   setBoard(initialBoards[player]);
                   setConsoleOutput([]);
-                  createGame(username, player == Player.White).then(
+                  createGame(session, player == Player.White).then(
                     (turnStatus) => {
                       setGameOngoing(true);
                       updateGame(turnStatus, []);
@@ -180,7 +187,7 @@ export default function Home({ username }: { username: string }) {
             <button
               className="button"
               onClick={() =>
-                submitCode(selectedLang, file, username).then(
+                submitCode(selectedLang, file, session).then(
                   () => {
                     alert("Code submitted !");
                     setLastSubmission({ lang: selectedLang, code: file });
@@ -200,7 +207,7 @@ export default function Home({ username }: { username: string }) {
                     "Are you sure ? Loading your last submission will overwrite your current code."
                   )
                 ) {
-                  loadSubmission(username).then(
+                  loadSubmission(session).then(
                     ({ lang, code }) => {
                       setLang(lang);
                       setFile(code);
@@ -270,7 +277,7 @@ export default function Home({ username }: { username: string }) {
             <div className="game">
               <div className="simulation">
                 <Board
-                  username={username}
+                  session={session}
                   player={player}
                   gameOngoing={gameOngoing}
                   currentTurn={currentTurn}
@@ -317,5 +324,5 @@ export default function Home({ username }: { username: string }) {
 }
 
 export const getServerSideProps = requireSession(async (context, session) => {
-  return { props: { username: session } };
+  return { props: { username: sessionUsername(session), session } };
 });
@@ -8,15 +8,20 @@ export default function Login() {
   const router = useRouter();
   const [cookies, setCookie] = useCookies([SESSION_COOKIE_NAME]);
   const [username, setUsername] = useState("");
+  const [password, setPassword] = useState("");
 
-  function attemptLogin(username: string) {
-    if (username !== "") {
-      login(username).then((success) => {
-        if (success) {
-          setCookie(SESSION_COOKIE_NAME, username);
+  function attemptLogin(username: string, password: string) {
+    if (username !== "" && password !== "") {
+      login(username, password).then((result) => {
+        if ("token" in result) {
+          setCookie(SESSION_COOKIE_NAME, result.token);
           router.push("/");
+        } else if (result.status === 401) {
+          alert("Wrong password");
+        } else if (result.status === 409) {
+          alert("This account has no password, ask an organizer to set one");
         } else {
-          alert("Username already taken");
+          alert("Invalid username");
         }
       });
     }
@@ -31,7 +36,12 @@ export default function Login() {
           placeholder="Username"
           onChange={(e) => setUsername(e.target.value)}
         />
-        <button onClick={() => attemptLogin(username)}>Go</button>
+        <input
+          type="password"
+          placeholder="Password"
+          onChange={(e) => setPassword(e.target.value)}
+        />
+        <button onClick={() => attemptLogin(username, password)}>Go</button>
       </div>
     </div>
   );
@@ -0,0 +1,359 @@
+use super::{
+    contest::{self, Contest, ContestStatus},
+    is_valid_name,
+    jobs::Job,
+    submissions::{Submission, SubmissionVersion},
+    AppState, Error, NewPassword, SharedState, State, User,
+};
+use crate::{
+    auth::{self, now},
+    config::config,
+    game::Variant,
+    storage::Collection,
+};
+use rocket::{
+    delete, get,
+    http::Status,
+    outcome::try_outcome,
+    post,
+    request::{self, FromRequest},
+    serde::json::Json,
+    Request,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Server-wide settings changed by the organizers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+pub struct Settings {
+    /// UNIX timestamp from which submissions cannot be uploaded anymore.
+    pub submissions_frozen_at: Option<u64>,
+}
+
+/// A user listed in `ADMINS`.
+pub struct Admin(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = Error;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let user = try_outcome!(req.guard::<User>().await);
+
+        if config().is_admin(&user.name) {
+            request::Outcome::Success(Admin(user))
+        } else {
+            request::Outcome::Error((Status::Forbidden, Error::Forbidden))
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserSummary {
+    name: String,
+    team: Option<String>,
+    disabled: bool,
+    admin: bool,
+    submission: Option<SubmissionVersion>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserDetails {
+    #[serde(flatten)]
+    summary: UserSummary,
+    submission_history: Vec<SubmissionVersion>,
+    /// Ids of every game of the user or their team.
+    games: Vec<u64>,
+}
+
+impl State {
+    fn user_summary(&self, name: &str) -> Result<UserSummary, Error> {
+        let user = self.users.get(name).ok_or(Error::NotFound)?;
+        let owner = user.team.as_deref().unwrap_or(name);
+
+        Ok(UserSummary {
+            name: user.name.clone(),
+            team: user.team.clone(),
+            disabled: user.disabled,
+            admin: config().is_admin(name),
+            submission: self
+                .submissions
+                .get(owner)
+                .filter(|s| s.version > 0)
+                .map(SubmissionVersion::from),
+        })
+    }
+
+    fn set_disabled(&mut self, name: &str, disabled: bool) -> Result<(), Error> {
+        let mut user = self.users.get(name).ok_or(Error::NotFound)?.clone();
+        user.disabled = disabled;
+
+        self.storage.save(Collection::Users, name, &user)?;
+        self.users.insert(name.to_owned(), user);
+
+        Ok(())
+    }
+
+    pub fn save_settings(&mut self, settings: Settings) -> Result<(), Error> {
+        self.storage
+            .save(Collection::Settings, "settings", &settings)?;
+        self.settings = settings;
+
+        Ok(())
+    }
+
+    /// Removes every version of a submission, files included.
+    fn delete_submission(&mut self, owner: &str) -> Result<(), Error> {
+        let active = self.submissions.remove(owner).ok_or(Error::NotFound)?;
+        let history = self.submission_history.remove(owner).unwrap_or_default();
+
+        self.storage.remove(Collection::Submissions, owner)?;
+        for submission in history.iter() {
+            self.storage.remove(
+                Collection::SubmissionHistory,
+                &format!("{owner}/{}", submission.version),
+            )?;
+        }
+
+        for submission in history.iter().chain([&active]) {
+            if let Err(e) = std::fs::remove_file(&submission.code) {
+                warn!(path = ?submission.code, error = %e, "failed to delete submission");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stops a running game, without result.
+    fn abort_game(&mut self, id: u64) -> Result<(), Error> {
+        let game = self
+            .matches
+            .remove(&id)
+            .or_else(|| self.games.remove(&id))
+            .ok_or(Error::NotFound)?;
+
+        // A game waiting on an AI keeps running in the background, but it is
+        // not reachable nor saved anymore.
+        self.retire_game(game);
+
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Vec<UserSummary>)),
+    security(("session" = [])),
+)]
+#[get("/admin/users")]
+pub async fn list_users(state: &AppState, _admin: Admin) -> Result<Json<Vec<UserSummary>>, Error> {
+    let lock = state.lock()?;
+    let mut users = lock
+        .users
+        .keys()
+        .map(|name| lock.user_summary(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    users.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Json(users))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = UserDetails)),
+    security(("session" = [])),
+)]
+#[get("/admin/users/<name>")]
+pub async fn get_user(
+    state: &AppState,
+    _admin: Admin,
+    name: &str,
+) -> Result<Json<UserDetails>, Error> {
+    let lock = state.lock()?;
+    let summary = lock.user_summary(name)?;
+    let owner = summary.team.clone().unwrap_or(name.to_owned());
+
+    Ok(Json(UserDetails {
+        submission_history: lock
+            .submission_history
+            .get(&owner)
+            .into_iter()
+            .flatten()
+            .map(SubmissionVersion::from)
+            .collect(),
+        games: lock
+            .all_games()
+            .filter(|g| g.info.involves(&owner))
+            .map(|g| g.info.id)
+            .collect(),
+        summary,
+    }))
+}
+
+/// Prevents the user from logging in or using their current sessions.
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/admin/users/<name>/disable")]
+pub async fn disable_user(state: &AppState, _admin: Admin, name: &str) -> Result<(), Error> {
+    state.lock()?.set_disabled(name, true)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/admin/users/<name>/enable")]
+pub async fn enable_user(state: &AppState, _admin: Admin, name: &str) -> Result<(), Error> {
+    state.lock()?.set_disabled(name, false)
+}
+
+/// Sets the password of a user, such as one registered before passwords were
+/// required, registering the user if needed.
+#[utoipa::path(
+    tag = "admin",
+    operation_id = "admin_set_password",
+    request_body = NewPassword,
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/admin/users/<name>/password", format = "json", data = "<password>")]
+pub async fn set_password(
+    state: &AppState,
+    _admin: Admin,
+    name: &str,
+    password: Json<NewPassword>,
+) -> Result<(), Error> {
+    if password.password.is_empty() {
+        return Err(Error::InvalidPassword);
+    }
+
+    let hash = auth::hash_password(&password.password);
+    let mut lock = state.lock()?;
+    if lock.users.contains_key(name) {
+        return lock.set_password(name, hash);
+    }
+
+    if !is_valid_name(name) {
+        return Err(Error::InvalidName);
+    }
+    if lock.teams.contains_key(name) {
+        return Err(Error::Conflict);
+    }
+    lock.save_submission(Submission::empty(name.to_owned())?)?;
+    lock.register(name, Some(hash))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[delete("/admin/submissions/<owner>")]
+pub async fn delete_submission(state: &AppState, _admin: Admin, owner: &str) -> Result<(), Error> {
+    state.lock()?.delete_submission(owner)
+}
+
+/// Rejects every upload from the given UNIX timestamp, or from now on.
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Settings)),
+    security(("session" = [])),
+)]
+#[post("/admin/submissions/freeze?<at>")]
+pub async fn freeze_submissions(
+    state: &AppState,
+    _admin: Admin,
+    at: Option<u64>,
+) -> Result<Json<Settings>, Error> {
+    let mut lock = state.lock()?;
+    let mut settings = lock.settings.clone();
+    settings.submissions_frozen_at = Some(at.unwrap_or_else(now));
+    lock.save_settings(settings.clone())?;
+
+    Ok(Json(settings))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Settings)),
+    security(("session" = [])),
+)]
+#[post("/admin/submissions/unfreeze")]
+pub async fn unfreeze_submissions(
+    state: &AppState,
+    _admin: Admin,
+) -> Result<Json<Settings>, Error> {
+    let mut lock = state.lock()?;
+    let mut settings = lock.settings.clone();
+    settings.submissions_frozen_at = None;
+    lock.save_settings(settings.clone())?;
+
+    Ok(Json(settings))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/admin/games/<id>/abort")]
+pub async fn abort_game(state: &AppState, _admin: Admin, id: u64) -> Result<(), Error> {
+    state.lock()?.abort_game(id)
+}
+
+/// Every job known to the server, most recent first.
+#[utoipa::path(
+    tag = "admin",
+    operation_id = "admin_list_jobs",
+    responses((status = 200, body = Vec<Job>)),
+    security(("session" = [])),
+)]
+#[get("/admin/jobs")]
+pub async fn list_jobs(state: &AppState, _admin: Admin) -> Result<Json<Vec<Job>>, Error> {
+    let mut jobs = state
+        .lock()?
+        .jobs
+        .jobs
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    jobs.sort_by_key(|j| Reverse(j.id));
+
+    Ok(Json(jobs))
+}
+
+/// Creates a contest between every non-empty submission. With `closes_at`,
+/// uploads are only accepted within the window and the contest starts when
+/// it closes, otherwise it starts immediately. The games are played in the
+/// background, follow them with `/contests/<name>` and `/games`.
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Contest)),
+    security(("session" = [])),
+)]
+#[post("/admin/contests?<name>&<variant>&<opens_at>&<closes_at>")]
+pub async fn start_contest(
+    state: &AppState,
+    _admin: Admin,
+    name: &str,
+    variant: Option<Variant>,
+    opens_at: Option<u64>,
+    closes_at: Option<u64>,
+) -> Result<Json<Contest>, Error> {
+    let contest =
+        state
+            .lock()?
+            .create_contest(name, variant.unwrap_or_default(), opens_at, closes_at)?;
+
+    if contest.status == ContestStatus::Running {
+        rocket::tokio::spawn(contest::run(SharedState::clone(state), contest.clone()));
+    }
+
+    Ok(Json(contest))
+}
@@ -0,0 +1,273 @@
+use super::{jobs, spectate::find_game, AppState, Error, User};
+use crate::{
+    engine::{self, MAN_VALUE},
+    game::{GameState, GameStatus, Move, MoveSequence, Position},
+    tablebase::Probe,
+};
+use rocket::{post, serde::json::Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Deepest search allowed for a position.
+const MAX_DEPTH: u32 = 6;
+/// Deepest search allowed for a game, as every one of its positions is
+/// analyzed.
+const MAX_GAME_DEPTH: u32 = 4;
+/// Score lost by a move, compared to the best one, for it to be a blunder.
+const BLUNDER_THRESHOLD: i32 = 2 * MAN_VALUE;
+
+/// What to analyze: either a position, or every position of a finished game.
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AnalysisRequest {
+    position: Option<GameState>,
+    /// Id of a finished game.
+    game: Option<u64>,
+    /// Number of turns searched ahead, the engine's own depth by default.
+    depth: Option<u32>,
+}
+
+/// What a job analyzes.
+#[derive(Debug)]
+pub enum Analyzed {
+    Position(GameState),
+    Game {
+        id: u64,
+        start: GameState,
+        sequences: Vec<Vec<Move>>,
+    },
+}
+
+impl Analyzed {
+    /// Id of the analyzed game, if any.
+    pub fn game(&self) -> Option<u64> {
+        match self {
+            Analyzed::Position(_) => None,
+            Analyzed::Game { id, .. } => Some(*id),
+        }
+    }
+
+    pub fn run(self, depth: u32) -> Vec<PositionAnalysis> {
+        match self {
+            Analyzed::Position(position) => vec![analyze_position(position, None, depth)],
+            Analyzed::Game {
+                start, sequences, ..
+            } => analyze_game(start, sequences, depth),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct MoveEvaluation {
+    moves: Vec<Move>,
+    /// `[row, column]` of the captured pieces.
+    #[schema(value_type = Vec<Vec<usize>>)]
+    captures: Vec<Position>,
+    /// Material balance expected after the search for the player to move, in
+    /// hundredths of a man. Wins score above 99000 (100000 minus the number of
+    /// plies to the win when it is found in the tablebase), losses below -99000.
+    score: i32,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PositionAnalysis {
+    position: GameState,
+    /// Every legal sequence of moves, best first.
+    moves: Vec<MoveEvaluation>,
+    /// The sequence played from this position, for games.
+    played: Option<MoveEvaluation>,
+    /// Whether the played sequence loses at least two men compared to the
+    /// best one.
+    blunder: bool,
+    /// Result of the position with best play, if it is in the endgame
+    /// tablebase.
+    tablebase: Option<Probe>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Analysis {
+    depth: u32,
+    positions: Vec<PositionAnalysis>,
+}
+
+fn evaluation((moves, captures): MoveSequence, score: i32) -> MoveEvaluation {
+    MoveEvaluation {
+        moves,
+        captures,
+        score,
+    }
+}
+
+fn analyze_position(position: GameState, played: Option<&[Move]>, depth: u32) -> PositionAnalysis {
+    let moves = if matches!(position.status, GameStatus::Running) {
+        engine::evaluate_moves(&position, depth)
+            .into_iter()
+            .map(|(sequence, score)| evaluation(sequence, score))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let played = played.and_then(|p| moves.iter().find(|m| m.moves == p).cloned());
+    let blunder = match (moves.first(), &played) {
+        (Some(best), Some(played)) => best.score - played.score >= BLUNDER_THRESHOLD,
+        _ => false,
+    };
+
+    PositionAnalysis {
+        tablebase: position.probe(),
+        position,
+        moves,
+        played,
+        blunder,
+    }
+}
+
+/// Every position of the game before one of its sequences of moves.
+fn analyze_game(start: GameState, sequences: Vec<Vec<Move>>, depth: u32) -> Vec<PositionAnalysis> {
+    let mut position = start;
+    let mut positions = vec![];
+
+    for moves in sequences {
+        let next = {
+            let mut next = position.clone();
+            next.apply_sequence(&moves).ok().map(|_| next)
+        };
+        positions.push(analyze_position(position, Some(&moves), depth));
+
+        match next {
+            Some(next) => position = next,
+            None => return positions,
+        }
+    }
+
+    positions.push(analyze_position(position, None, depth));
+    positions
+}
+
+/// Evaluates every legal sequence of moves of a position, or of each position
+/// of a finished game, with the built-in engine. The analysis runs as a job of
+/// the user.
+#[utoipa::path(
+    tag = "analysis",
+    request_body = AnalysisRequest,
+    responses((status = 200, body = Analysis)),
+    security(("session" = [])),
+)]
+#[post("/analysis", format = "json", data = "<request>")]
+pub async fn analyze(
+    state: &AppState,
+    user: User,
+    request: Json<AnalysisRequest>,
+) -> Result<Json<Analysis>, Error> {
+    let request = request.into_inner();
+    let max_depth = match request.game {
+        Some(_) => MAX_GAME_DEPTH,
+        None => MAX_DEPTH,
+    };
+    let depth = request.depth.unwrap_or(engine::DEPTH.min(max_depth));
+    if !(1..=max_depth).contains(&depth) {
+        return Err(Error::InvalidAnalysis);
+    }
+
+    let analyzed = match (request.position, request.game) {
+        (Some(position), None) => {
+            let size = position.variant.rules().size;
+            if position.board.len() != size || position.board.iter().any(|r| r.len() != size) {
+                return Err(Error::InvalidAnalysis);
+            }
+
+            Analyzed::Position(position)
+        }
+        (None, Some(id)) => {
+            let snapshot = find_game(state, id)?.snapshot();
+            if matches!(snapshot.game.status, GameStatus::Running) {
+                return Err(Error::GameNotFinished);
+            }
+
+            let start = GameState::new(snapshot.game.variant);
+            let sequences = snapshot
+                .history
+                .into_iter()
+                .filter(|t| t.action.is_none())
+                .map(|t| t.moves)
+                .collect();
+
+            Analyzed::Game {
+                id,
+                start,
+                sequences,
+            }
+        }
+        _ => return Err(Error::InvalidAnalysis),
+    };
+
+    let positions = jobs::queue_analysis(state, user.owner(), analyzed, depth)?
+        .await
+        .map_err(|_| Error::InfrastructureFailure)?;
+
+    Ok(Json(Analysis { depth, positions }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{analyze_game, analyze_position};
+    use crate::game::{
+        empty_board, GameState, GameStatus, Move, Piece, PieceType, Player, Variant,
+    };
+
+    fn piece(type_: PieceType, player: Player) -> Option<Piece> {
+        Some(Piece { type_, player })
+    }
+
+    #[test]
+    fn flags_blunders() {
+        let mut board = empty_board(10);
+        board[9][4] = piece(PieceType::King, Player::White);
+        board[6][1] = piece(PieceType::Man, Player::White);
+        board[4][7] = piece(PieceType::Man, Player::Black);
+        let position = GameState::from_position(
+            Variant::International,
+            board,
+            Player::White,
+            GameStatus::Running,
+        );
+
+        let safe = [Move {
+            from: (6, 1),
+            to: (5, 0),
+        }];
+        let hanging = [Move {
+            from: (9, 4),
+            to: (5, 8),
+        }];
+
+        assert!(!analyze_position(position.clone(), Some(&safe), 2).blunder);
+
+        let analysis = analyze_position(position, Some(&hanging), 2);
+        assert!(analysis.blunder);
+        assert_eq!(analysis.played.unwrap().moves, hanging);
+    }
+
+    #[test]
+    fn analyzes_every_position_of_a_game() {
+        let start = GameState::default();
+        let sequences = vec![
+            vec![Move {
+                from: (6, 1),
+                to: (5, 2),
+            }],
+            vec![Move {
+                from: (3, 2),
+                to: (4, 3),
+            }],
+        ];
+
+        let positions = analyze_game(start, sequences, 1);
+
+        assert_eq!(positions.len(), 3);
+        assert!(positions[..2].iter().all(|p| p.played.is_some()));
+        assert!(positions[2].played.is_none());
+        assert_eq!(positions[2].position.current_player, Player::White);
+        assert_eq!(positions[2].moves.len(), 9);
+    }
+}
@@ -0,0 +1,17 @@
+use crate::{
+    book::{book, BookMove},
+    game::GameState,
+};
+use rocket::{post, serde::json::Json};
+
+/// The sequences of moves of the opening book for a position, most played
+/// first.
+#[utoipa::path(
+    tag = "book",
+    request_body = GameState,
+    responses((status = 200, body = Vec<BookMove>)),
+)]
+#[post("/book", format = "json", data = "<position>")]
+pub async fn book_moves(position: Json<GameState>) -> Json<Vec<BookMove>> {
+    Json(book().moves(&position).to_vec())
+}
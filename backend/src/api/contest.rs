@@ -1 +1,487 @@
+use super::{
+    is_valid_name, jobs,
+    play::{Game, GameHandle, GameInfo, Side},
+    submissions::Submission,
+    AppState, Error, SharedState, State,
+};
+use crate::{
+    auth::now,
+    config::config,
+    game::{GameState, GameStatus, Player, Variant},
+    storage::Collection,
+    tablebase::{self, Wdl},
+};
+use rocket::{
+    futures::{stream, StreamExt},
+    get,
+    serde::json::Json,
+    tokio::time::sleep,
+};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, sync::Arc, time::Duration};
+use tracing::{error, field, info, info_span, Instrument, Span};
+use utoipa::ToSchema;
 
+/// Number of turns after which a contest game is declared a draw.
+const MAX_TURNS: usize = 300;
+
+/// How often the scheduler checks for contests whose submissions closed.
+const SCHEDULER_PERIOD: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ContestStatus {
+    /// Waiting for its submission window to close.
+    Scheduled,
+    Running,
+    Finished,
+    /// The server stopped before the end of the contest.
+    Interrupted,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Standing {
+    pub name: String,
+    /// 2 points per victory, 1 per draw.
+    pub points: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// The version of a submission playing a contest.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Entry {
+    pub name: String,
+    pub version: u32,
+}
+
+/// A round-robin tournament between the active submissions, where every pair
+/// of participants plays once with each color.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Contest {
+    pub name: String,
+    pub status: ContestStatus,
+    #[serde(default)]
+    pub created_at: u64,
+    /// UNIX timestamp at which the submission window opens, as announced to
+    /// the participants. Versions uploaded earlier still count.
+    #[serde(default)]
+    pub opens_at: Option<u64>,
+    /// UNIX timestamp at which the active version of each submission, the
+    /// latest one uploaded by then, is snapshotted to start the contest.
+    /// Uploads are rejected from then until the contest is over.
+    #[serde(default)]
+    pub closes_at: Option<u64>,
+    /// Snapshot of the active submissions, taken when the contest starts.
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// Ids of the games played so far.
+    pub games: Vec<u64>,
+    /// Sorted by decreasing points.
+    pub standings: Vec<Standing>,
+    /// Rules of the contest's games.
+    #[serde(default)]
+    pub variant: Variant,
+}
+
+impl Contest {
+    /// Whether uploads are accepted at the given time, which they are except
+    /// from the close of the submission window until the contest is over.
+    pub fn accepts_submissions(&self, now: u64) -> bool {
+        !matches!(
+            self.status,
+            ContestStatus::Scheduled | ContestStatus::Running
+        ) || self.closes_at.is_none_or(|at| now < at)
+    }
+
+    /// Whether a version uploaded at the given time may enter the contest.
+    fn in_window(&self, uploaded_at: u64) -> bool {
+        self.closes_at.is_none_or(|at| uploaded_at <= at)
+    }
+
+    fn record(&mut self, white: &str, black: &str, status: &GameStatus) {
+        for standing in self.standings.iter_mut() {
+            let player = if standing.name == white {
+                Player::White
+            } else if standing.name == black {
+                Player::Black
+            } else {
+                continue;
+            };
+
+            match status.winner() {
+                Some(p) if p == player => {
+                    standing.wins += 1;
+                    standing.points += 2;
+                }
+                Some(_) => standing.losses += 1,
+                _ => {
+                    standing.draws += 1;
+                    standing.points += 1;
+                }
+            }
+        }
+
+        self.standings.sort_by_key(|s| Reverse(s.points));
+    }
+}
+
+impl State {
+    pub fn save_contest(&mut self, contest: Contest) -> Result<(), Error> {
+        self.storage
+            .save(Collection::Contests, &contest.name, &contest)?;
+        self.contests.insert(contest.name.clone(), contest);
+
+        Ok(())
+    }
+
+    /// Registers a new contest. Without `closes_at`, the contest starts
+    /// immediately: its games must then be played with `run`.
+    pub fn create_contest(
+        &mut self,
+        name: &str,
+        variant: Variant,
+        opens_at: Option<u64>,
+        closes_at: Option<u64>,
+    ) -> Result<Contest, Error> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidName);
+        }
+        if self.contests.contains_key(name) {
+            return Err(Error::Conflict);
+        }
+        if opens_at.is_some() && closes_at.is_none()
+            || opens_at.zip(closes_at).is_some_and(|(o, c)| o >= c)
+            || closes_at.is_some_and(|c| c <= now())
+        {
+            return Err(Error::InvalidWindow);
+        }
+
+        let mut contest = Contest {
+            name: name.to_owned(),
+            status: ContestStatus::Scheduled,
+            created_at: now(),
+            opens_at,
+            closes_at,
+            entries: vec![],
+            started_at: None,
+            finished_at: None,
+            games: vec![],
+            standings: vec![],
+            variant,
+        };
+
+        if closes_at.is_none() {
+            self.snapshot_entries(&mut contest);
+        }
+
+        self.save_contest(contest.clone())?;
+
+        Ok(contest)
+    }
+
+    /// Freezes the latest version of every submission uploaded before the
+    /// contest's window closed into the contest, and marks it as running.
+    fn snapshot_entries(&self, contest: &mut Contest) {
+        let mut entries = self
+            .submission_history
+            .iter()
+            .filter(|(name, _)| !self.users.get(*name).is_some_and(|u| u.disabled))
+            .filter_map(|(name, history)| {
+                let submission = history
+                    .iter()
+                    .rev()
+                    .find(|s| contest.in_window(s.uploaded_at))?;
+
+                Some(Entry {
+                    name: name.clone(),
+                    version: submission.version,
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        contest.standings = entries
+            .iter()
+            .map(|e| Standing {
+                name: e.name.clone(),
+                points: 0,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+            })
+            .collect();
+        contest.entries = entries;
+        contest.status = ContestStatus::Running;
+        contest.started_at = Some(now());
+    }
+
+    /// Starts the scheduled contests whose submission window has closed.
+    fn start_due_contests(&mut self, now: u64) -> Result<Vec<Contest>, Error> {
+        let mut due = self
+            .contests
+            .values()
+            .filter(|c| c.status == ContestStatus::Scheduled)
+            .filter(|c| c.closes_at.is_some_and(|at| at <= now))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for contest in due.iter_mut() {
+            self.snapshot_entries(contest);
+            self.save_contest(contest.clone())?;
+        }
+
+        Ok(due)
+    }
+
+    /// Rejects uploads while submissions are frozen or outside the window of a
+    /// scheduled contest.
+    pub fn check_submissions_open(&self, now: u64) -> Result<(), Error> {
+        if let Some(at) = self.settings.submissions_frozen_at.filter(|at| now >= *at) {
+            return Err(Error::SubmissionsClosed {
+                contest: None,
+                opens_at: None,
+                closes_at: Some(at),
+            });
+        }
+
+        match self.contests.values().find(|c| !c.accepts_submissions(now)) {
+            Some(contest) => Err(Error::SubmissionsClosed {
+                contest: Some(contest.name.clone()),
+                opens_at: contest.opens_at,
+                closes_at: contest.closes_at,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The version of a submission entered in a contest.
+    pub fn entry_submission(&self, contest: &str, name: &str) -> Option<Submission> {
+        let entry = self
+            .contests
+            .get(contest)?
+            .entries
+            .iter()
+            .find(|e| e.name == name)?;
+
+        self.submission_history
+            .get(name)?
+            .iter()
+            .find(|s| s.version == entry.version)
+            .cloned()
+    }
+}
+
+/// Plays a game between two submissions, queuing each turn as a job. Returns
+/// the id and result of the game, or `None` if it has been aborted.
+async fn play_match(
+    state: &SharedState,
+    contest: &str,
+    white: &Entry,
+    black: &Entry,
+) -> Result<Option<(u64, GameStatus)>, Error> {
+    let info = GameInfo {
+        id: state.lock()?.new_game_id(),
+        owner: contest.to_owned(),
+        white: Side::Bot(white.name.clone()),
+        black: Side::Bot(black.name.clone()),
+        contest: Some(contest.to_owned()),
+        hints: false,
+    };
+    let id = info.id;
+    let variant = state
+        .lock()?
+        .contests
+        .get(contest)
+        .map_or_else(Variant::default, |c| c.variant);
+    let handle = Arc::new(GameHandle::new(info, Game::new(GameState::new(variant))));
+    state.lock()?.matches.insert(id, handle.clone());
+    Span::current().record("game", id);
+
+    let mut turns = 0;
+
+    loop {
+        if !state.lock()?.matches.contains_key(&id) {
+            return Ok(None);
+        }
+
+        {
+            let mut game = handle.game.lock().await;
+            if !matches!(game.checkers.status, GameStatus::Running) {
+                break;
+            }
+
+            if turns == MAX_TURNS {
+                game.checkers.status = GameStatus::Draw;
+                game.publish_state();
+                break;
+            }
+
+            // Endings of the tablebase are adjudicated to their result with
+            // best play.
+            if let Some(wdl) = game.checkers.probe_wdl() {
+                let player = game.checkers.current_player;
+                game.checkers.status = match wdl {
+                    Wdl::Win => GameStatus::Victory(player),
+                    Wdl::Draw => GameStatus::Draw,
+                    Wdl::Loss => GameStatus::Victory(player.opponent()),
+                };
+                game.publish_state();
+                break;
+            }
+        }
+
+        // The bot forfeits if it fails or its submission is gone, any other
+        // error stops the game.
+        let (_, result) = jobs::queue_bot_move(state, &handle)?;
+        match result.await.map_err(|_| Error::InfrastructureFailure)? {
+            Ok(_) | Err(Error::AIFailed { .. }) | Err(Error::NotFound) => (),
+            Err(e) => {
+                state.lock()?.matches.remove(&id);
+                return Err(e);
+            }
+        }
+
+        turns += 1;
+    }
+
+    let mut lock = state.lock()?;
+    if lock.matches.remove(&id).is_none() {
+        return Ok(None);
+    }
+    lock.retire_game(handle.clone());
+
+    Ok(Some((id, handle.state().status)))
+}
+
+/// Plays every game of a running contest, `WORKERS` at a time, and records
+/// their results as they finish. The turns of the bots are queued as jobs of
+/// the contest, which share the workers fairly with the users.
+pub async fn run(state: SharedState, mut contest: Contest) {
+    // Games are only adjudicated alike once the tablebase is loaded.
+    if !tablebase::is_settled() {
+        info!(contest = contest.name, "waiting for the endgame tablebase");
+        tablebase::settled().await;
+    }
+
+    let entries = contest.entries.clone();
+    info!(
+        contest = contest.name,
+        entries = entries.len(),
+        "contest started"
+    );
+
+    let name = contest.name.clone();
+    let pairs = entries
+        .iter()
+        .flat_map(|white| {
+            entries
+                .iter()
+                .filter(move |b| b.name != white.name)
+                .map(move |black| (white.clone(), black.clone()))
+        })
+        .collect::<Vec<_>>();
+    let mut results = stream::iter(pairs)
+        .map(|(white, black)| {
+            let span = info_span!(
+                "match",
+                contest = %name,
+                white = %white.name,
+                black = %black.name,
+                game = field::Empty,
+            );
+            let state = state.clone();
+            let name = name.clone();
+            async move {
+                let result = play_match(&state, &name, &white, &black).await;
+                (white, black, result)
+            }
+            .instrument(span)
+        })
+        .buffer_unordered(config().workers.max(1));
+
+    while let Some((white, black, result)) = results.next().await {
+        match result {
+            Ok(Some((id, status))) => {
+                contest.record(&white.name, &black.name, &status);
+                contest.games.push(id);
+            }
+            Ok(None) => (),
+            Err(e) => error!(
+                contest = contest.name,
+                white = white.name,
+                black = black.name,
+                error = ?e,
+                "contest game failed"
+            ),
+        }
+
+        let mut lock = match state.lock() {
+            Ok(lock) => lock,
+            Err(_) => return,
+        };
+        if let Err(e) = lock.save_contest(contest.clone()) {
+            error!(contest = contest.name, error = ?e, "failed to save contest");
+        }
+    }
+
+    contest.status = ContestStatus::Finished;
+    contest.finished_at = Some(now());
+
+    info!(contest = contest.name, "contest finished");
+    if let Ok(mut lock) = state.lock() {
+        if let Err(e) = lock.save_contest(contest.clone()) {
+            error!(contest = contest.name, error = ?e, "failed to save contest");
+        }
+    }
+}
+
+/// Starts the scheduled contests when their submission window closes. Runs
+/// for the whole life of the server.
+pub async fn schedule(state: SharedState) {
+    loop {
+        let due = match state.lock() {
+            Ok(mut lock) => lock.start_due_contests(now()),
+            Err(_) => return,
+        };
+
+        match due {
+            Ok(due) => {
+                for contest in due {
+                    rocket::tokio::spawn(run(state.clone(), contest));
+                }
+            }
+            Err(e) => error!(error = ?e, "failed to start the scheduled contests"),
+        }
+
+        sleep(SCHEDULER_PERIOD).await;
+    }
+}
+
+#[utoipa::path(
+    tag = "contests",
+    responses((status = 200, body = Vec<Contest>)),
+)]
+#[get("/contests")]
+pub async fn list_contests(state: &AppState) -> Result<Json<Vec<Contest>>, Error> {
+    let mut contests = state.lock()?.contests.values().cloned().collect::<Vec<_>>();
+    contests.sort_by_key(|c| Reverse(c.created_at));
+
+    Ok(Json(contests))
+}
+
+#[utoipa::path(
+    tag = "contests",
+    responses((status = 200, body = Contest)),
+)]
+#[get("/contests/<name>")]
+pub async fn get_contest(state: &AppState, name: &str) -> Result<Json<Contest>, Error> {
+    let lock = state.lock()?;
+
+    Ok(Json(
+        lock.contests.get(name).ok_or(Error::NotFound)?.clone(),
+    ))
+}
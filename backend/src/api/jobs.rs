@@ -0,0 +1,429 @@
+use super::{
+    analysis::{Analyzed, PositionAnalysis},
+    play::{bot_submission, Game, GameHandle, Side},
+    submissions::Submission,
+    AppState, Error, SharedState, State, User,
+};
+use crate::{auth::now, config::config, game::GameStatus};
+use rocket::{
+    get,
+    serde::json::Json,
+    tokio::{
+        sync::{oneshot, Notify},
+        task::spawn_blocking,
+        time::sleep,
+    },
+};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{debug, info_span, warn, Instrument};
+use utoipa::ToSchema;
+
+/// Number of finished jobs kept for the status endpoints.
+const FINISHED_JOBS_KEPT: usize = 1000;
+
+/// Delay before retrying after an infrastructure failure, multiplied by the
+/// number of retries so far.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum JobKind {
+    /// The turn of a bot, in a user's game or a contest game.
+    BotMove,
+    /// The analysis of a position or of a finished game.
+    Analysis,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Job {
+    pub id: u64,
+    /// User, team or contest on behalf of which the job runs. Queued jobs are
+    /// served in turn between owners.
+    pub owner: String,
+    #[serde(flatten)]
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    /// Number of times the bot was restarted after an infrastructure failure.
+    pub retries: u32,
+    /// Game played or analyzed by the job.
+    pub game: Option<u64>,
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+}
+
+/// What a worker executes for a job, along with where to send the result.
+#[derive(Debug)]
+pub enum Task {
+    BotMove {
+        game: Arc<GameHandle>,
+        done: oneshot::Sender<Result<String, Error>>,
+    },
+    Analysis {
+        analyzed: Analyzed,
+        depth: u32,
+        done: oneshot::Sender<Vec<PositionAnalysis>>,
+    },
+}
+
+/// The queue of jobs waiting for a worker, along with the status of recent
+/// jobs.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    next_id: u64,
+    pub jobs: BTreeMap<u64, Job>,
+    tasks: HashMap<u64, Task>,
+    /// Queued jobs of each owner, oldest first.
+    queues: HashMap<String, VecDeque<u64>>,
+    /// Owners with queued jobs, in the order they will be served.
+    turns: VecDeque<String>,
+    notify: Arc<Notify>,
+}
+
+impl Jobs {
+    pub fn push(&mut self, owner: &str, kind: JobKind, game: Option<u64>, task: Task) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+
+        self.jobs.insert(
+            id,
+            Job {
+                id,
+                owner: owner.to_owned(),
+                kind,
+                status: JobStatus::Queued,
+                error: None,
+                retries: 0,
+                game,
+                created_at: now(),
+                started_at: None,
+                finished_at: None,
+            },
+        );
+        self.tasks.insert(id, task);
+
+        let queue = self.queues.entry(owner.to_owned()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(owner.to_owned());
+        }
+        queue.push_back(id);
+
+        self.notify.notify_one();
+
+        id
+    }
+
+    /// Takes the oldest job of the next owner in turn.
+    fn next(&mut self) -> Option<(u64, Task)> {
+        let owner = self.turns.pop_front()?;
+        let queue = self.queues.get_mut(&owner)?;
+        let id = queue.pop_front()?;
+
+        if queue.is_empty() {
+            self.queues.remove(&owner);
+        } else {
+            self.turns.push_back(owner);
+        }
+
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.status = JobStatus::Running;
+            job.started_at = Some(now());
+        }
+
+        Some((id, self.tasks.remove(&id)?))
+    }
+
+    fn finish(&mut self, id: u64, error: Option<String>) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.status = match error {
+                Some(_) => JobStatus::Failed,
+                None => JobStatus::Done,
+            };
+            job.error = error;
+            job.finished_at = Some(now());
+        }
+
+        let finished = self
+            .jobs
+            .values()
+            .filter(|j| j.finished_at.is_some())
+            .map(|j| j.id)
+            .collect::<Vec<_>>();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(FINISHED_JOBS_KEPT))
+        {
+            self.jobs.remove(id);
+        }
+    }
+
+    fn retried(&mut self, id: u64) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.retries += 1;
+        }
+    }
+
+    /// Number of jobs waiting for a worker.
+    pub fn queued(&self) -> usize {
+        self.tasks.len()
+    }
+}
+
+impl Error {
+    /// Whether the error comes from the server or Docker rather than from the
+    /// bot, in which case the bot's turn can be retried.
+    pub fn is_infrastructure(&self) -> bool {
+        matches!(self, Error::IO | Error::InfrastructureFailure)
+    }
+}
+
+impl State {
+    /// Whether the game is still played, by users or in a contest.
+    fn is_playing(&self, game: &Arc<GameHandle>) -> bool {
+        self.is_active_game(game)
+            || self
+                .matches
+                .get(&game.info.id)
+                .is_some_and(|g| Arc::ptr_eq(g, game))
+    }
+}
+
+/// Queues the turn of the bot in a game, on behalf of the game's owner.
+pub fn queue_bot_move(
+    state: &SharedState,
+    game: &Arc<GameHandle>,
+) -> Result<(u64, oneshot::Receiver<Result<String, Error>>), Error> {
+    let (done, result) = oneshot::channel();
+    let id = state.lock()?.jobs.push(
+        &game.info.owner,
+        JobKind::BotMove,
+        Some(game.info.id),
+        Task::BotMove {
+            game: game.clone(),
+            done,
+        },
+    );
+
+    Ok((id, result))
+}
+
+/// Queues an analysis on behalf of the user or their team.
+pub fn queue_analysis(
+    state: &SharedState,
+    owner: &str,
+    analyzed: Analyzed,
+    depth: u32,
+) -> Result<oneshot::Receiver<Vec<PositionAnalysis>>, Error> {
+    let (done, result) = oneshot::channel();
+    let game = analyzed.game();
+    state.lock()?.jobs.push(
+        owner,
+        JobKind::Analysis,
+        game,
+        Task::Analysis {
+            analyzed,
+            depth,
+            done,
+        },
+    );
+
+    Ok(result)
+}
+
+/// Plays the turn of the AI, restarting it when the failure comes from the
+/// infrastructure rather than from the bot.
+async fn play_ai(
+    state: &SharedState,
+    job: u64,
+    game: &mut Game,
+    submission: Submission,
+) -> Result<String, Error> {
+    let mut retries = 0;
+
+    loop {
+        match game.play_ai(submission.clone()).await {
+            Err(e) if e.is_infrastructure() && retries < config().job_retries => {
+                retries += 1;
+                warn!(error = ?e, retries, "infrastructure failure, retrying");
+                state.lock()?.jobs.retried(job);
+                sleep(RETRY_DELAY * retries).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn play_bot_move(
+    state: &SharedState,
+    job: u64,
+    handle: &Arc<GameHandle>,
+) -> Result<String, Error> {
+    let mut game = handle.game.lock().await;
+
+    // The game may have been stopped, replaced or played while queued.
+    let player = game.checkers.current_player;
+    let side = handle.info.side(player);
+    if !matches!(game.checkers.status, GameStatus::Running)
+        || !side.is_ai()
+        || !state.lock()?.is_playing(handle)
+    {
+        return Ok(String::new());
+    }
+
+    let result = match side {
+        Side::Engine => game.play_engine().await,
+        _ => match bot_submission(state, &handle.info, player) {
+            Ok(submission) => play_ai(state, job, &mut game, submission).await,
+            Err(e) => Err(e),
+        },
+    };
+
+    if matches!(result, Err(Error::AIFailed { .. }) | Err(Error::NotFound))
+        && matches!(game.checkers.status, GameStatus::Running)
+    {
+        game.forfeit(player);
+    }
+    drop(game);
+
+    state.lock()?.save_game(handle)?;
+
+    result
+}
+
+async fn work(state: SharedState, notify: Arc<Notify>) {
+    loop {
+        let next = match state.lock() {
+            Ok(mut lock) => lock.jobs.next(),
+            Err(_) => return,
+        };
+        let Some((id, task)) = next else {
+            notify.notified().await;
+            continue;
+        };
+
+        // The job is marked as finished before the result is sent, so that
+        // whoever waits for it sees the final status.
+        let finish = |error: Option<String>| {
+            match &error {
+                Some(error) => warn!(error, "job failed"),
+                None => debug!("job done"),
+            }
+
+            if let Ok(mut lock) = state.lock() {
+                lock.jobs.finish(id, error);
+            }
+        };
+
+        match task {
+            Task::BotMove { game, done } => {
+                async {
+                    let result = play_bot_move(&state, id, &game).await;
+                    finish(result.as_ref().err().map(|e| format!("{e:?}")));
+                    let _ = done.send(result);
+                }
+                .instrument(info_span!("job", id))
+                .instrument(game.span())
+                .await
+            }
+            Task::Analysis {
+                analyzed,
+                depth,
+                done,
+            } => {
+                async {
+                    match spawn_blocking(move || analyzed.run(depth)).await {
+                        Ok(positions) => {
+                            finish(None);
+                            let _ = done.send(positions);
+                        }
+                        Err(e) => finish(Some(format!("{e:?}"))),
+                    }
+                }
+                .instrument(info_span!("job", id, depth))
+                .await
+            }
+        }
+    }
+}
+
+/// Spawns the worker pool, and queues the turns of the bots that were
+/// interrupted by a restart.
+pub fn start(state: &SharedState) -> Result<(), Error> {
+    let lock = state.lock()?;
+
+    for _ in 0..config().workers.max(1) {
+        rocket::tokio::spawn(work(state.clone(), lock.jobs.notify.clone()));
+    }
+
+    let pending = lock
+        .games
+        .values()
+        .filter(|g| {
+            let game = g.state();
+            matches!(game.status, GameStatus::Running) && g.info.side(game.current_player).is_ai()
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    drop(lock);
+
+    for game in pending {
+        queue_bot_move(state, &game)?;
+    }
+
+    Ok(())
+}
+
+/// The jobs of the user or their team, most recent first.
+#[utoipa::path(
+    tag = "jobs",
+    responses((status = 200, body = Vec<Job>)),
+    security(("session" = [])),
+)]
+#[get("/jobs")]
+pub async fn list_jobs(state: &AppState, user: User) -> Result<Json<Vec<Job>>, Error> {
+    let mut jobs = state
+        .lock()?
+        .jobs
+        .jobs
+        .values()
+        .filter(|j| j.owner == user.owner())
+        .cloned()
+        .collect::<Vec<_>>();
+    jobs.sort_by_key(|j| Reverse(j.id));
+
+    Ok(Json(jobs))
+}
+
+#[utoipa::path(
+    tag = "jobs",
+    responses((status = 200, body = Job)),
+    security(("session" = [])),
+)]
+#[get("/jobs/<id>")]
+pub async fn get_job(state: &AppState, user: User, id: u64) -> Result<Json<Job>, Error> {
+    state
+        .lock()?
+        .jobs
+        .jobs
+        .get(&id)
+        .filter(|j| j.owner == user.owner() || config().is_admin(&user.name))
+        .cloned()
+        .map(Json)
+        .ok_or(Error::NotFound)
+}
@@ -1,89 +1,547 @@
-use play::Game;
+use crate::{
+    auth,
+    config::config,
+    game::{GameStatus, Move},
+    logging::RequestSpan,
+    storage::{self, Collection, Storage},
+};
+use admin::Settings;
+use contest::{Contest, ContestStatus};
+use jobs::Jobs;
+use play::{GameEvent, GameHandle, GameRecord};
 use rocket::{
-    http::Header,
+    catch, catchers,
+    http::{Header, Status},
     post,
     request::{self, FromRequest},
     response::Responder,
-    routes, Request, Response, Route,
+    routes,
+    serde::json::Json,
+    Catcher, Request, Response, Route,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::read_dir,
+    io::Cursor,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
 };
-use serde::Serialize;
-use std::{collections::HashMap, fs::read_dir, io::Cursor, str::FromStr, sync::Arc};
 use submissions::{Language, Submission};
+use teams::Team;
+use tracing::{debug, error};
+use utoipa::ToSchema;
 
-use crate::{config::config, game::Move};
-
+pub mod admin;
+pub mod analysis;
+pub mod book;
 pub mod contest;
+pub mod jobs;
+pub mod openapi;
 pub mod play;
+pub mod spectate;
 pub mod submissions;
+pub mod teams;
 
-#[derive(Default, Debug)]
+/// Number of finished games kept in memory, for listing and spectating. Older
+/// ones are read from the storage when requested by id.
+const FINISHED_GAMES_KEPT: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserRecord {
+    pub name: String,
+    /// Only users registered before passwords were required have none. They
+    /// cannot log in until they or an admin set one.
+    #[serde(default)]
+    pub password_hash: Option<String>,
+    #[serde(default)]
+    pub team: Option<String>,
+    /// Disabled users cannot log in nor use their sessions.
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Debug)]
 pub struct State {
+    pub storage: Arc<dyn Storage>,
+    pub users: HashMap<String, UserRecord>,
+    pub teams: HashMap<String, Team>,
+    /// Active submission of each user or team.
     pub submissions: HashMap<String, Submission>,
-    pub games: HashMap<String, Arc<rocket::tokio::sync::Mutex<Game>>>,
+    /// Every uploaded version of the submissions, oldest first.
+    pub submission_history: HashMap<String, Vec<Submission>>,
+    /// Last version handed out to an upload of each owner, including the
+    /// uploads still being written.
+    reserved_versions: HashMap<String, u32>,
+    /// Games of the users, by id, until they are stopped or replaced by a new
+    /// game against the same opponent.
+    pub games: HashMap<u64, Arc<GameHandle>>,
+    /// Bot-vs-bot games in progress, by id.
+    pub matches: HashMap<u64, Arc<GameHandle>>,
+    /// The most recent games that have been replaced or stopped after
+    /// reaching a result, oldest first.
+    pub finished_games: VecDeque<Arc<GameHandle>>,
+    pub next_game_id: u64,
+    pub contests: HashMap<String, Contest>,
+    pub settings: Settings,
+    pub jobs: Jobs,
 }
 
 impl State {
     pub fn load() -> Result<Self, Error> {
-        Ok(State {
-            submissions: read_dir(config().data_dir.clone())?
-                .filter_map(|d| d.ok())
-                .filter(|d| d.file_type().is_ok_and(|t| t.is_file()))
-                .map(|d| {
-                    let file_name = d.file_name();
-                    let binding = dbg!(file_name.into_string().unwrap());
-                    let (name, lang) = binding.rsplit_once('.').unwrap();
-
-                    (
-                        name.to_string(),
-                        Submission {
-                            name: name.to_string(),
-                            lang: Language::from_str(lang).unwrap(),
-                            code: d.path(),
-                        },
-                    )
-                })
+        let storage = storage::open(config().storage, Path::new(&config().data_dir))?;
+        let mut state = Self::load_from(storage)?;
+
+        for submission in legacy_submissions()? {
+            if !state.submissions.contains_key(&submission.name) {
+                state.register(&submission.name, None)?;
+                state.save_submission(submission)?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    pub fn load_from(storage: Arc<dyn Storage>) -> Result<Self, Error> {
+        let mut state = State {
+            users: storage
+                .load_all::<UserRecord>(Collection::Users)?
+                .into_iter()
+                .map(|u| (u.name.clone(), u))
                 .collect(),
-            ..Default::default()
-        })
+            teams: storage
+                .load_all::<Team>(Collection::Teams)?
+                .into_iter()
+                .map(|t| (t.name.clone(), t))
+                .collect(),
+            submissions: storage
+                .load_all::<Submission>(Collection::Submissions)?
+                .into_iter()
+                .map(|s| (s.name.clone(), s))
+                .collect(),
+            submission_history: HashMap::new(),
+            reserved_versions: HashMap::new(),
+            games: HashMap::new(),
+            matches: HashMap::new(),
+            finished_games: VecDeque::new(),
+            next_game_id: 0,
+            contests: HashMap::new(),
+            settings: storage
+                .load_all::<Settings>(Collection::Settings)?
+                .pop()
+                .unwrap_or_default(),
+            jobs: Jobs::default(),
+            storage: storage.clone(),
+        };
+
+        for mut contest in storage.load_all::<Contest>(Collection::Contests)? {
+            if contest.status == ContestStatus::Running {
+                contest.status = ContestStatus::Interrupted;
+            }
+            state.save_contest(contest)?;
+        }
+
+        for submission in storage.load_all::<Submission>(Collection::SubmissionHistory)? {
+            state
+                .submission_history
+                .entry(submission.name.clone())
+                .or_default()
+                .push(submission);
+        }
+        for history in state.submission_history.values_mut() {
+            history.sort_by_key(|s| s.version);
+        }
+
+        let mut records = storage.load_all::<GameRecord>(Collection::Games)?;
+        records.sort_by_key(|r| r.info.id);
+
+        for record in records {
+            state.next_game_id = state.next_game_id.max(record.info.id);
+
+            if record.active {
+                let game = Arc::new(GameHandle::restore(record));
+                state.games.insert(game.info.id, game);
+            } else {
+                // Finished games used to be kept along with the active ones.
+                let key = record.info.id.to_string();
+                storage.save(Collection::FinishedGames, &key, &record)?;
+                storage.remove(Collection::Games, &key)?;
+            }
+        }
+
+        let mut finished = storage
+            .keys(Collection::FinishedGames)?
+            .iter()
+            .filter_map(|k| k.parse::<u64>().ok())
+            .collect::<Vec<_>>();
+        finished.sort_unstable();
+        state.next_game_id = state
+            .next_game_id
+            .max(finished.last().copied().unwrap_or_default());
+
+        for id in finished.iter().rev().take(FINISHED_GAMES_KEPT).rev() {
+            if let Some(game) = state.load_finished_game(*id)? {
+                state.finished_games.push_back(game);
+            }
+        }
+
+        Ok(state)
+    }
+
+    pub fn register(&mut self, name: &str, password_hash: Option<String>) -> Result<(), Error> {
+        let user = UserRecord {
+            name: name.to_owned(),
+            password_hash,
+            team: None,
+            disabled: false,
+        };
+
+        self.storage.save(Collection::Users, name, &user)?;
+        self.users.insert(name.to_owned(), user);
+
+        Ok(())
+    }
+
+    pub fn set_password(&mut self, name: &str, password_hash: String) -> Result<(), Error> {
+        let mut user = self.users.get(name).ok_or(Error::NotFound)?.clone();
+        user.password_hash = Some(password_hash);
+
+        self.storage.save(Collection::Users, name, &user)?;
+        self.users.insert(name.to_owned(), user);
+
+        Ok(())
+    }
+
+    /// Records the submission in the history unless it is the empty
+    /// placeholder, and makes it the active one of its owner unless a later
+    /// version was saved first.
+    pub fn save_submission(&mut self, submission: Submission) -> Result<(), Error> {
+        if submission.version > 0 {
+            self.storage.save(
+                Collection::SubmissionHistory,
+                &format!("{}/{}", submission.name, submission.version),
+                &submission,
+            )?;
+            let history = self
+                .submission_history
+                .entry(submission.name.clone())
+                .or_default();
+            let index = history.partition_point(|s| s.version < submission.version);
+            history.insert(index, submission.clone());
+        }
+
+        if self
+            .submissions
+            .get(&submission.name)
+            .is_some_and(|s| s.version > submission.version)
+        {
+            return Ok(());
+        }
+
+        self.storage
+            .save(Collection::Submissions, &submission.name, &submission)?;
+        self.submissions.insert(submission.name.clone(), submission);
+
+        Ok(())
+    }
+
+    /// Hands out the version of a new upload, which no other upload will get
+    /// even if this one is not saved yet.
+    pub fn reserve_submission_version(&mut self, owner: &str) -> u32 {
+        let saved = self
+            .submission_history
+            .get(owner)
+            .and_then(|h| h.last())
+            .map_or(0, |s| s.version);
+        let reserved = self.reserved_versions.entry(owner.to_owned()).or_default();
+
+        *reserved = (*reserved).max(saved) + 1;
+        *reserved
+    }
+
+    pub fn new_game_id(&mut self) -> u64 {
+        self.next_game_id += 1;
+        self.next_game_id
+    }
+
+    /// Whether the game is still in `games`.
+    pub fn is_active_game(&self, game: &Arc<GameHandle>) -> bool {
+        self.games
+            .get(&game.info.id)
+            .is_some_and(|g| Arc::ptr_eq(g, game))
+    }
+
+    /// Persists a game, unless it has been replaced or stopped meanwhile.
+    pub fn save_game(&self, game: &Arc<GameHandle>) -> Result<(), Error> {
+        if self.is_active_game(game) {
+            self.storage.save(
+                Collection::Games,
+                &game.info.id.to_string(),
+                &game.record(true),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Notifies the subscribers of a game removed from `games`, and keeps it
+    /// for spectators if it has a result.
+    pub fn retire_game(&mut self, game: Arc<GameHandle>) {
+        game.publish(GameEvent::Stopped);
+
+        let key = game.info.id.to_string();
+        let result = if matches!(game.state().status, GameStatus::Running) {
+            self.storage.remove(Collection::Games, &key)
+        } else {
+            let result = self
+                .storage
+                .save(Collection::FinishedGames, &key, &game.record(false))
+                .and_then(|_| self.storage.remove(Collection::Games, &key));
+
+            self.finished_games.push_back(game);
+            if self.finished_games.len() > FINISHED_GAMES_KEPT {
+                self.finished_games.pop_front();
+            }
+            result
+        };
+
+        if let Err(e) = result {
+            error!(game = key, error = ?e, "failed to retire game");
+        }
+    }
+
+    fn load_finished_game(&self, id: u64) -> Result<Option<Arc<GameHandle>>, Error> {
+        Ok(self
+            .storage
+            .load::<GameRecord>(Collection::FinishedGames, &id.to_string())?
+            .map(|record| Arc::new(GameHandle::restore(record))))
+    }
+
+    /// Any game by id, including finished games no longer kept in memory.
+    pub fn find_game(&self, id: u64) -> Result<Option<Arc<GameHandle>>, Error> {
+        match self.all_games().find(|g| g.info.id == id) {
+            Some(game) => Ok(Some(game.clone())),
+            None => self.load_finished_game(id),
+        }
+    }
+
+    /// Every game in memory: the ongoing ones and the most recent finished
+    /// ones.
+    pub fn all_games(&self) -> impl Iterator<Item = &Arc<GameHandle>> {
+        self.games
+            .values()
+            .chain(self.matches.values())
+            .chain(self.finished_games.iter())
     }
 }
 
-pub type AppState = rocket::State<std::sync::Mutex<State>>;
+/// Submissions stored in `DATA_DIR` as `<name>.<lang>` before the state was
+/// persisted in a `Storage`.
+fn legacy_submissions() -> Result<Vec<Submission>, Error> {
+    Ok(read_dir(config().data_dir.clone())?
+        .filter_map(|d| d.ok())
+        .filter(|d| d.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|d| {
+            let file_name = d.file_name().into_string().ok()?;
+            let (name, lang) = file_name.rsplit_once('.')?;
+
+            Some(Submission {
+                name: name.to_string(),
+                lang: Language::from_str(lang).ok()?,
+                code: d.path(),
+                version: 1,
+                uploaded_by: None,
+                uploaded_at: 0,
+            })
+        })
+        .collect())
+}
+
+pub type SharedState = Arc<std::sync::Mutex<State>>;
+pub type AppState = rocket::State<SharedState>;
 
 pub fn routes() -> Vec<Route> {
     routes![
         submissions::get_submission,
         submissions::post_submission,
+        submissions::get_submission_history,
         play::get_game,
+        play::events,
+        play::hint,
         play::start,
         play::stop,
+        play::act,
         play::play,
+        set_password,
+        spectate::list_games,
+        spectate::get_game,
+        spectate::events,
+        analysis::analyze,
+        book::book_moves,
+        teams::get_team,
+        teams::create_team,
+        teams::join_team,
+        teams::leave_team,
+        contest::list_contests,
+        contest::get_contest,
+        jobs::list_jobs,
+        jobs::get_job,
+        admin::list_users,
+        admin::get_user,
+        admin::disable_user,
+        admin::enable_user,
+        admin::set_password,
+        admin::delete_submission,
+        admin::freeze_submissions,
+        admin::unfreeze_submissions,
+        admin::abort_game,
+        admin::list_jobs,
+        admin::start_contest,
         login,
+        openapi::openapi,
+        openapi::docs,
     ]
 }
 
-#[post("/login?<name>")]
-pub async fn login(name: &str, state: &AppState) -> rocket::http::Status {
-    let mut lock = state.lock().unwrap();
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct Credentials {
+    name: String,
+    password: String,
+    /// When registering, directly joins the team with this code.
+    join_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct NewPassword {
+    pub password: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Session {
+    token: String,
+    expires_at: u64,
+}
+
+/// Names of users and teams are used in file names, so they are restricted.
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.chars().count())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Registers the user if the name is free, or logs into an existing account.
+/// New users may directly join a team with its code.
+#[utoipa::path(
+    tag = "auth",
+    request_body = Credentials,
+    responses((status = 200, body = Session)),
+)]
+#[post("/login", format = "json", data = "<credentials>")]
+pub async fn login(
+    credentials: Json<Credentials>,
+    state: &AppState,
+) -> Result<Json<Session>, Error> {
+    let Credentials {
+        name,
+        password,
+        join_code,
+    } = credentials.into_inner();
+    let (name, join_code) = (name.as_str(), join_code.as_deref());
+
+    // Hashing is slow on purpose, so it is done without holding the lock.
+    let user = state.lock()?.users.get(name).cloned();
+
+    match user {
+        Some(UserRecord { disabled: true, .. }) => return Err(Error::Forbidden),
+        Some(UserRecord {
+            password_hash: Some(hash),
+            ..
+        }) => {
+            if !auth::verify_password(&password, &hash) {
+                return Err(Error::Unauthorized);
+            }
+        }
+        Some(_) => return Err(Error::PasswordNotSet),
+        None => {
+            if !is_valid_name(name) {
+                return Err(Error::InvalidName);
+            }
+            if password.is_empty() {
+                return Err(Error::InvalidPassword);
+            }
+            if !config().may_register(name, &password) {
+                return Err(Error::Forbidden);
+            }
+
+            let password_hash = Some(auth::hash_password(&password));
+
+            let mut lock = state.lock()?;
+            if lock.users.contains_key(name) || lock.teams.contains_key(name) {
+                return Err(Error::Conflict);
+            }
+            if let Some(code) = join_code {
+                let team = lock.teams.values().find(|t| t.join_code == code);
+                match team {
+                    None => return Err(Error::NotFound),
+                    Some(t) if t.members.len() >= config().team_size => {
+                        return Err(Error::TeamFull)
+                    }
+                    Some(_) => (),
+                }
+            }
+
+            lock.save_submission(Submission::empty(name.to_string())?)?;
+            lock.register(name, password_hash)?;
 
-    if lock.submissions.contains_key(name) {
-        rocket::http::Status::Conflict
-    } else if let Ok(submission) = Submission::empty(name.to_string()) {
-        lock.submissions.insert(name.to_string(), submission);
-        rocket::http::Status::Ok
-    } else {
-        rocket::http::Status::InternalServerError
+            if let Some(code) = join_code {
+                lock.join_team(name, code)?;
+            }
+        }
     }
+
+    let (token, claims) = auth::issue_token(name);
+
+    Ok(Json(Session {
+        token,
+        expires_at: claims.exp,
+    }))
 }
 
-#[derive(Debug, Serialize)]
+/// Sets or changes the password of the user.
+#[utoipa::path(
+    tag = "auth",
+    request_body = NewPassword,
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/password", format = "json", data = "<password>")]
+pub async fn set_password(
+    state: &AppState,
+    user: User,
+    password: Json<NewPassword>,
+) -> Result<(), Error> {
+    if password.password.is_empty() {
+        return Err(Error::InvalidPassword);
+    }
+
+    let hash = auth::hash_password(&password.password);
+    state.lock()?.set_password(&user.name, hash)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum AIError {
     InvalidMove,
     InvalidOutput,
     EmptySubmission,
+    /// The AI ran out of time.
+    Timeout,
 }
 
+/// Errors returned by the API. The fields of a variant are sent as the
+/// `details` of the error's JSON body.
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum Error {
@@ -91,7 +549,12 @@ pub enum Error {
     Poison,
     InvalidLanguage,
     NotFound,
-    InvalidMove,
+    InvalidMove {
+        #[serde(rename = "move")]
+        move_: Vec<Move>,
+        /// Every sequence the player could have played instead.
+        legal_moves: Vec<Vec<Move>>,
+    },
     AIFailed {
         error: AIError,
         ai_output: String,
@@ -99,47 +562,266 @@ pub enum Error {
         move_: Option<Vec<Move>>,
     },
     Unauthorized,
-    GameAlreadyInProgress,
+    Conflict,
+    InvalidName,
+    InvalidPassword,
+    /// The account was registered before passwords were required.
+    PasswordNotSet,
+    TeamFull,
+    Forbidden,
+    /// Uploads are rejected because of a freeze or a contest's submission
+    /// window.
+    SubmissionsClosed {
+        contest: Option<String>,
+        opens_at: Option<u64>,
+        closes_at: Option<u64>,
+    },
+    InvalidWindow,
+    /// The game is over, or there is nothing to answer or take back.
+    InvalidAction,
+    /// The player's time has run out, which ended the game.
+    OutOfTime,
+    InvalidTimeControl,
+    /// A game against the same opponent is still running.
+    GameAlreadyInProgress {
+        game: u64,
+    },
+    NotYourTurn,
+    /// The analysis needs either a position or a game, and a valid depth.
+    InvalidAnalysis,
+    GameNotFinished,
+    /// Joining a team would leave the user's running games without a player.
+    GamesInProgress,
+    HintsDisabled,
+    /// Docker failed to run a bot, or the worker running it disappeared.
+    InfrastructureFailure,
 }
 
-impl<'r, 'o: 'r> Responder<'r, 'o> for Error {
-    fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
-        use rocket::http::Status;
-
-        Err(match self {
+impl Error {
+    pub fn status(&self) -> Status {
+        match self {
             Error::IO | Error::Poison => Status::InternalServerError,
+            Error::InfrastructureFailure => Status::ServiceUnavailable,
             Error::NotFound => Status::NotFound,
-            Error::InvalidMove | Error::GameAlreadyInProgress | Error::InvalidLanguage => {
-                Status::BadRequest
+            Error::InvalidMove { .. }
+            | Error::InvalidLanguage
+            | Error::InvalidName
+            | Error::InvalidPassword
+            | Error::InvalidWindow
+            | Error::InvalidTimeControl
+            | Error::InvalidAnalysis => Status::BadRequest,
+            Error::AIFailed { .. } => Status::NotAcceptable,
+            Error::Unauthorized => Status::Unauthorized,
+            Error::Forbidden | Error::SubmissionsClosed { .. } | Error::HintsDisabled => {
+                Status::Forbidden
             }
-            e @ Error::AIFailed { .. } => {
-                return Ok(Response::build()
-                    .status(Status::NotAcceptable)
-                    .header(Header::new("Content-Type", "application/json"))
-                    .streamed_body(Cursor::new(serde_json::to_string(&e).unwrap()))
-                    .finalize())
+            Error::Conflict
+            | Error::PasswordNotSet
+            | Error::TeamFull
+            | Error::GameAlreadyInProgress { .. }
+            | Error::InvalidAction
+            | Error::OutOfTime
+            | Error::NotYourTurn
+            | Error::GameNotFinished
+            | Error::GamesInProgress => Status::Conflict,
+        }
+    }
+
+    /// Machine-readable identifier of the error, stable across versions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::IO | Error::Poison => "internal",
+            Error::InvalidLanguage => "invalidLanguage",
+            Error::NotFound => "notFound",
+            Error::InvalidMove { .. } => "invalidMove",
+            Error::AIFailed { .. } => "aiFailed",
+            Error::Unauthorized => "unauthorized",
+            Error::Conflict => "conflict",
+            Error::InvalidName => "invalidName",
+            Error::InvalidPassword => "invalidPassword",
+            Error::PasswordNotSet => "passwordNotSet",
+            Error::TeamFull => "teamFull",
+            Error::Forbidden => "forbidden",
+            Error::SubmissionsClosed { .. } => "submissionsClosed",
+            Error::InvalidWindow => "invalidWindow",
+            Error::InvalidAction => "invalidAction",
+            Error::OutOfTime => "outOfTime",
+            Error::InvalidTimeControl => "invalidTimeControl",
+            Error::GameAlreadyInProgress { .. } => "gameAlreadyInProgress",
+            Error::NotYourTurn => "notYourTurn",
+            Error::InvalidAnalysis => "invalidAnalysis",
+            Error::GameNotFinished => "gameNotFinished",
+            Error::GamesInProgress => "gamesInProgress",
+            Error::HintsDisabled => "hintsDisabled",
+            Error::InfrastructureFailure => "infrastructureFailure",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::IO | Error::Poison => "Internal server error".to_owned(),
+            Error::InvalidLanguage => "Unknown language, expected cpp, java or python".to_owned(),
+            Error::NotFound => "Not found".to_owned(),
+            Error::InvalidMove { legal_moves, .. } => format!(
+                "Illegal sequence of moves, {} legal sequences are available",
+                legal_moves.len()
+            ),
+            Error::AIFailed { error, .. } => match error {
+                AIError::InvalidMove => "The AI played an illegal move".to_owned(),
+                AIError::InvalidOutput => "The AI failed or printed an invalid move".to_owned(),
+                AIError::EmptySubmission => "The submission is empty".to_owned(),
+                AIError::Timeout => "The AI ran out of time".to_owned(),
+            },
+            Error::Unauthorized => {
+                "Wrong password, or missing, invalid or expired session token".to_owned()
             }
-            Error::Unauthorized => Status::Unauthorized,
-        })
+            Error::Conflict => {
+                "The name is already taken, or the user is already in a team".to_owned()
+            }
+            Error::InvalidName => "Names must have 1 to 32 letters, digits, '-' or '_'".to_owned(),
+            Error::InvalidPassword => "The password must not be empty".to_owned(),
+            Error::PasswordNotSet => {
+                "The account has no password, an admin must set one".to_owned()
+            }
+            Error::TeamFull => "The team is full".to_owned(),
+            Error::Forbidden => "Forbidden".to_owned(),
+            Error::SubmissionsClosed {
+                contest: Some(contest),
+                ..
+            } => format!("Submissions are closed for contest {contest}"),
+            Error::SubmissionsClosed { contest: None, .. } => "Submissions are frozen".to_owned(),
+            Error::InvalidWindow => {
+                "The submission window must close in the future, after it opens".to_owned()
+            }
+            Error::OutOfTime => "The time of the player has run out".to_owned(),
+            Error::InvalidTimeControl => "The initial time must be positive".to_owned(),
+            Error::InvalidAction => {
+                "The game is over, or there is nothing to answer or take back".to_owned()
+            }
+            Error::GameAlreadyInProgress { game } => {
+                format!("Game {game} against the same opponent is still in progress")
+            }
+            Error::NotYourTurn => "It is not your turn to play".to_owned(),
+            Error::InvalidAnalysis => {
+                "Give either a position of the variant's size or a game, and a depth from 1 to 6 (4 for a game)"
+                    .to_owned()
+            }
+            Error::GameNotFinished => "Only finished games can be analyzed".to_owned(),
+            Error::GamesInProgress => {
+                "Finish or stop your running games before joining a team".to_owned()
+            }
+            Error::HintsDisabled => "Hints are disabled for this game".to_owned(),
+            Error::InfrastructureFailure => "The AI could not be run, try again later".to_owned(),
+        }
+    }
+}
+
+/// The JSON body of every error response.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    /// Fields specific to the error, if any.
+    #[schema(value_type = Option<Object>)]
+    pub details: serde_json::Value,
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for ErrorBody {
+    fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        Response::build()
+            .header(Header::new("Content-Type", "application/json"))
+            .streamed_body(Cursor::new(serde_json::to_string(&self).unwrap()))
+            .ok()
     }
 }
 
+impl<'r, 'o: 'r> Responder<'r, 'o> for Error {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        RequestSpan::of(req).in_scope(|| match self {
+            Error::IO | Error::Poison | Error::InfrastructureFailure => {
+                error!(error = ?self, "request failed")
+            }
+            _ => debug!(error = ?self, "request failed"),
+        });
+
+        let body = ErrorBody {
+            code: self.code().to_owned(),
+            message: self.message(),
+            details: serde_json::to_value(&self).unwrap_or_default(),
+        };
+
+        Response::build_from(body.respond_to(req)?)
+            .status(self.status())
+            .ok()
+    }
+}
+
+/// Errors raised by Rocket itself (unknown routes, invalid parameters or
+/// bodies) and by the request guards, in the same format as `Error`.
+#[catch(default)]
+fn default_catcher(status: Status, _: &Request) -> (Status, ErrorBody) {
+    let code = match status.code {
+        400 => "badRequest",
+        401 => "unauthorized",
+        403 => "forbidden",
+        404 => "notFound",
+        422 => "unprocessable",
+        500..=599 => "internal",
+        _ => "unknown",
+    };
+
+    let body = ErrorBody {
+        code: code.to_owned(),
+        message: status.reason_lossy().to_owned(),
+        details: serde_json::Value::Null,
+    };
+
+    (status, body)
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![default_catcher]
+}
+
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        dbg!(value);
+        error!(error = %value, "I/O error");
+        Self::IO
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        error!(error = %value, "serialization error");
+        Self::IO
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(value: rusqlite::Error) -> Self {
+        error!(error = %value, "database error");
         Self::IO
     }
 }
 
 impl<Guard> From<std::sync::PoisonError<Guard>> for Error {
-    fn from(value: std::sync::PoisonError<Guard>) -> Self {
-        dbg!(value);
+    fn from(_: std::sync::PoisonError<Guard>) -> Self {
+        error!("the state's lock is poisoned");
         Self::Poison
     }
 }
 
 pub struct User {
     pub name: String,
+    pub team: Option<String>,
+}
+
+impl User {
+    /// Name under which the user's submissions and games are stored: their
+    /// team's if they have one.
+    pub fn owner(&self) -> &str {
+        self.team.as_deref().unwrap_or(&self.name)
+    }
 }
 
 #[rocket::async_trait]
@@ -147,16 +829,36 @@ impl<'r> FromRequest<'r> for User {
     type Error = self::Error;
 
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        if let Some(name) = req
+        let Some(token) = req
             .headers()
             .get_one("Authorization")
             .and_then(|header| header.strip_prefix("Bearer "))
-        {
-            request::Outcome::Success(User {
-                name: name.to_owned(),
-            })
-        } else {
-            request::Outcome::Error((rocket::http::Status::Unauthorized, Error::Unauthorized))
+        else {
+            return request::Outcome::Error((Status::Unauthorized, Error::Unauthorized));
+        };
+
+        let name = match auth::verify_token(token) {
+            Some(claims) => claims.sub,
+            None if config().auth_dev_mode => token.to_owned(),
+            None => return request::Outcome::Error((Status::Unauthorized, Error::Unauthorized)),
+        };
+
+        let record = req
+            .rocket()
+            .state::<SharedState>()
+            .and_then(|state| state.lock().ok())
+            .and_then(|state| state.users.get(&name).cloned());
+
+        match record {
+            Some(record) if record.disabled => {
+                request::Outcome::Error((Status::Forbidden, Error::Forbidden))
+            }
+            Some(record) => request::Outcome::Success(User {
+                name,
+                team: record.team,
+            }),
+            None if config().auth_dev_mode => request::Outcome::Success(User { name, team: None }),
+            None => request::Outcome::Error((Status::Unauthorized, Error::Unauthorized)),
         }
     }
 }
@@ -0,0 +1,134 @@
+use super::{admin, analysis, book, contest, jobs, play, spectate, submissions, teams, ErrorBody};
+use crate::{clock, game, metrics};
+use rocket::{get, http::ContentType, response::content::RawHtml};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ContentBuilder, Ref, ResponseBuilder,
+    },
+    Modify, OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "S4S checkers",
+        description = "Play checkers against your AI, and make it compete against the others'."
+    ),
+    paths(
+        super::login,
+        super::set_password,
+        submissions::get_submission,
+        submissions::post_submission,
+        submissions::get_submission_history,
+        play::get_game,
+        play::events,
+        play::hint,
+        play::start,
+        play::play,
+        play::stop,
+        play::act,
+        spectate::list_games,
+        spectate::get_game,
+        spectate::events,
+        analysis::analyze,
+        book::book_moves,
+        teams::get_team,
+        teams::create_team,
+        teams::join_team,
+        teams::leave_team,
+        contest::list_contests,
+        contest::get_contest,
+        jobs::list_jobs,
+        jobs::get_job,
+        admin::list_users,
+        admin::get_user,
+        admin::disable_user,
+        admin::enable_user,
+        admin::set_password,
+        admin::delete_submission,
+        admin::freeze_submissions,
+        admin::unfreeze_submissions,
+        admin::abort_game,
+        admin::list_jobs,
+        admin::start_contest,
+        metrics::metrics,
+    ),
+    components(schemas(
+        ErrorBody,
+        spectate::StatusFilter,
+        play::Opponent,
+        clock::ClockKind,
+        game::Variant
+    )),
+    modifiers(&Conventions)
+)]
+pub struct ApiDoc;
+
+/// Adds what is shared by every endpoint: the session token and the error
+/// format.
+struct Conventions;
+
+impl Modify for Conventions {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "session",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+
+        let error = ResponseBuilder::new()
+            .description("An error, identified by its `code`")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ErrorBody")))
+                    .build(),
+            )
+            .build();
+
+        for item in openapi.paths.paths.values_mut() {
+            for operation in [&mut item.get, &mut item.post, &mut item.delete]
+                .into_iter()
+                .flatten()
+            {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_owned())
+                    .or_insert_with(|| error.clone().into());
+            }
+        }
+    }
+}
+
+#[get("/openapi.json")]
+pub async fn openapi() -> (ContentType, String) {
+    (ContentType::JSON, ApiDoc::openapi().to_json().unwrap())
+}
+
+/// Swagger UI for `/openapi.json`, loaded from a CDN.
+#[get("/docs")]
+pub async fn docs() -> RawHtml<&'static str> {
+    RawHtml(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>S4S checkers API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>"##,
+    )
+}
@@ -1,55 +1,674 @@
-use super::{submissions::Submission, AppState, Error, User};
-use crate::game::{GameState, GameStatus, Move, Player, TurnStatus};
+use super::{
+    jobs,
+    submissions::{Submission, READY_MESSAGE},
+    AppState, Error, SharedState, State, User,
+};
+use crate::{
+    book::book,
+    clock::{now_ms, Clock, ClockKind, TimeControl},
+    config::config,
+    engine,
+    game::{GameState, GameStatus, Move, Player, Position, TurnStatus, Variant},
+    metrics,
+};
 use regex::Regex;
 use rocket::{
-    futures::{io::BufReader, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt},
+    futures::{io::BufReader, AsyncBufReadExt, AsyncWriteExt},
     get, post,
+    response::stream::{Event, EventStream},
     serde::json::Json,
-    tokio::sync::Mutex,
+    tokio::{
+        join, select,
+        sync::{
+            broadcast::{self, error::RecvError},
+            watch, Mutex,
+        },
+        task::spawn_blocking,
+        time::{sleep, timeout},
+    },
+    FromForm, FromFormField, Shutdown,
 };
-use std::sync::{Arc, LazyLock};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, info_span, Instrument, Span};
+use utoipa::{IntoParams, ToSchema};
 
+/// Moves printed by a bot, as `<from>,<to>;` where cells are written as their
+/// row and column with one digit each, or two on boards larger than 10×10.
 static AI_OUTPUT_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new("^(\\d{2},\\d{2};)+$").unwrap());
+    LazyLock::new(|| Regex::new("^(\\d{2}(\\d{2})?,\\d{2}(\\d{2})?;)+$").unwrap());
+
+/// What a bot is sent after the board when its opponent offers a draw, and
+/// prints instead of its moves to accept it.
+const DRAW_MESSAGE: &str = "draw";
+
+/// Prefix of the line giving a bot the rules of the game, before the board,
+/// unless they are the international ones.
+const VARIANT_MESSAGE: &str = "variant";
+
+/// Prefix of the line giving a bot its remaining time and its opponent's, in
+/// milliseconds, when the game has a clock.
+const TIME_MESSAGE: &str = "time";
+
+/// Prefix of the lines giving a bot the sequences of moves of the opening
+/// book for the position, along with their weights.
+const BOOK_MESSAGE: &str = "book";
+
+/// Number of events a slow subscriber may lag behind before missing some.
+const EVENT_CAPACITY: usize = 256;
+
+/// Exit code of `docker run` when the container could not be started.
+const DOCKER_ERROR_CODE: i32 = 125;
+
+/// Longest time a bot may take to start, compilation included, which is not
+/// counted on its clock.
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Interval at which the clocks of the users are checked for flag falls.
+const CLOCK_TICK: Duration = Duration::from_millis(100);
+
+/// What a player can do in a game besides moving.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Action {
+    /// Ends the game with a victory of the opponent.
+    Resign,
+    /// Stands until the opponent answers or plays. Offering a draw when the
+    /// opponent already did accepts it.
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    /// Asks to undo the player's last move, along with the opponent's reply.
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+}
+
+/// A sequence of moves played by one of the players, or one of their actions,
+/// as kept in the history.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Turn {
+    pub player: Player,
+    pub moves: Vec<Move>,
+    /// `[row, column]` of the captured pieces.
+    #[schema(value_type = Vec<Vec<usize>>)]
+    pub captures: Vec<Position>,
+    /// The action played instead of moves, if any.
+    #[serde(default)]
+    pub action: Option<Action>,
+}
+
+/// Everything that happens in a game, as pushed to `/game/events` subscribers.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum GameEvent {
+    /// A sequence of moves has been applied.
+    Move(Turn),
+    /// A player has resigned, or offered, answered or requested something.
+    Action(Turn),
+    /// The state of the game after a change (start, move, status change).
+    State { game: GameState },
+    /// The remaining time of the players after a change, if the game has a
+    /// clock.
+    Clock { clock: Clock },
+    /// A chunk of what the AI printed on stderr, sent as soon as it is read.
+    AiOutput { output: String },
+    /// The game has been stopped and will not emit anything anymore.
+    Stopped,
+}
+
+/// The state of a game along with every turn played so far.
+#[derive(Debug, Serialize, Clone)]
+pub struct Snapshot {
+    pub game: GameState,
+    pub history: Vec<Turn>,
+    pub clock: Option<Clock>,
+}
 
 #[derive(Debug)]
 pub struct Game {
-    checkers: GameState,
-    human_player: Player,
+    pub checkers: GameState,
+    history: Vec<Turn>,
+    pub clock: Option<Clock>,
+    events: broadcast::Sender<GameEvent>,
+    snapshot: watch::Sender<Snapshot>,
+}
+
+/// Who plays one of the colors of a game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[serde(tag = "type", content = "name", rename_all = "camelCase")]
+pub enum Side {
+    /// A user or team, playing through the API.
+    Human(String),
+    /// The active submission of a user or team.
+    Bot(String),
+    /// The built-in engine.
+    Engine,
+}
+
+impl Side {
+    /// The user or team playing the side, if any.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Side::Human(name) | Side::Bot(name) => Some(name),
+            Side::Engine => None,
+        }
+    }
+
+    /// Whether the side is played by the server rather than through the API.
+    pub fn is_ai(&self) -> bool {
+        !matches!(self, Side::Human(_))
+    }
+}
+
+/// The rules and clock of a new game. Games have no clock unless `initial_ms`
+/// is given.
+#[derive(FromForm, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct SetupParams {
+    /// Rules of the game, `international` by default.
+    variant: Option<Variant>,
+    /// Kind of clock, `fischer` by default.
+    clock: Option<ClockKind>,
+    /// Initial time of each player, in milliseconds.
+    initial_ms: Option<u64>,
+    /// Increment per move, in milliseconds.
+    increment_ms: Option<u64>,
+    /// Whether the players may ask for hints, `true` by default. Games between
+    /// users never allow them, as the opponent would not have agreed to it.
+    hints: Option<bool>,
+}
+
+impl SetupParams {
+    fn time_control(&self) -> Result<Option<TimeControl>, Error> {
+        match self.initial_ms {
+            None => Ok(None),
+            Some(0) => Err(Error::InvalidTimeControl),
+            Some(initial_ms) => Ok(Some(TimeControl {
+                kind: self.clock.unwrap_or_default(),
+                initial_ms,
+                increment_ms: self.increment_ms.unwrap_or(0),
+            })),
+        }
+    }
+}
+
+/// Who a user plays against when starting a game.
+#[derive(FromFormField, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+pub enum Opponent {
+    /// The active submission of the user or their team.
+    #[default]
+    Bot,
+    /// The built-in engine.
+    Engine,
+    /// Another user or team.
+    User,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "SerializedGameInfo")]
+pub struct GameInfo {
+    pub id: u64,
+    /// User or team who started the game, or contest the game belongs to.
+    pub owner: String,
+    pub white: Side,
+    pub black: Side,
+    pub contest: Option<String>,
+    /// Whether the players may ask for hints.
+    pub hints: bool,
+}
+
+/// A `GameInfo` as serialized. Games saved before their sides were recorded
+/// only have the color of their owner, who played against their own bot, and
+/// games saved before hints existed allowed them unless played between users
+/// or in a contest.
+#[derive(Deserialize)]
+struct SerializedGameInfo {
+    id: u64,
+    owner: String,
+    white: Option<Side>,
+    black: Option<Side>,
+    human_player: Option<Player>,
+    #[serde(default)]
+    contest: Option<String>,
+    hints: Option<bool>,
 }
 
-fn convert_cell_id(id: &[char]) -> (usize, usize) {
-    (id[0] as usize - '0' as usize, id[1] as usize - '0' as usize)
+impl From<SerializedGameInfo> for GameInfo {
+    fn from(info: SerializedGameInfo) -> Self {
+        let human = Side::Human(info.owner.clone());
+        let bot = Side::Bot(info.owner.clone());
+        let (white, black) = match (info.white, info.black, info.human_player) {
+            (Some(white), Some(black), _) => (white, black),
+            (_, _, Some(Player::Black)) => (bot, human),
+            _ => (human, bot),
+        };
+
+        let between_users = !white.is_ai() && !black.is_ai();
+        let hints = info
+            .hints
+            .unwrap_or(info.contest.is_none() && !between_users);
+
+        Self {
+            id: info.id,
+            owner: info.owner,
+            white,
+            black,
+            contest: info.contest,
+            hints,
+        }
+    }
+}
+
+impl GameInfo {
+    pub fn side(&self, player: Player) -> &Side {
+        match player {
+            Player::White => &self.white,
+            Player::Black => &self.black,
+        }
+    }
+
+    /// Whether the given user or team is the owner of, or plays in, the game.
+    pub fn involves(&self, name: &str) -> bool {
+        self.owner == name || self.white.name() == Some(name) || self.black.name() == Some(name)
+    }
+
+    /// The color the given user or team plays through the API, if any.
+    pub fn player_of(&self, name: &str) -> Option<Player> {
+        [Player::White, Player::Black]
+            .into_iter()
+            .find(|p| matches!(self.side(*p), Side::Human(n) if n == name))
+    }
+
+    /// Whether the game opposes the two sides, whichever their colors.
+    pub fn opposes(&self, a: &Side, b: &Side) -> bool {
+        (&self.white == a && &self.black == b) || (&self.white == b && &self.black == a)
+    }
+}
+
+/// A game as persisted in the `Storage`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GameRecord {
+    #[serde(flatten)]
+    pub info: GameInfo,
+    pub game: GameState,
+    pub history: Vec<Turn>,
+    #[serde(default)]
+    pub clock: Option<Clock>,
+    /// Whether the game is still in `State::games`, rather than stopped or
+    /// replaced.
+    pub active: bool,
+}
+
+/// Shared access to a game. The metadata and channels are kept outside of the
+/// mutex so that spectators do not have to wait for the AI to finish its turn.
+#[derive(Debug)]
+pub struct GameHandle {
+    pub info: GameInfo,
+    pub game: Mutex<Game>,
+    events: broadcast::Sender<GameEvent>,
+    snapshot: watch::Receiver<Snapshot>,
+}
+
+impl GameHandle {
+    pub fn new(info: GameInfo, game: Game) -> Self {
+        Self {
+            info,
+            events: game.events.clone(),
+            snapshot: game.snapshot.subscribe(),
+            game: Mutex::new(game),
+        }
+    }
+
+    pub fn restore(record: GameRecord) -> Self {
+        let mut game = Game::new(record.game);
+        game.history = record.history;
+        game.clock = record.clock;
+        // The time during which the server was down is not counted.
+        if let Some(clock) = &mut game.clock {
+            clock.restart(now_ms());
+        }
+        game.publish_state();
+
+        Self::new(record.info, game)
+    }
+
+    pub fn record(&self, active: bool) -> GameRecord {
+        let snapshot = self.snapshot();
+
+        GameRecord {
+            info: self.info.clone(),
+            game: snapshot.game,
+            history: snapshot.history,
+            clock: snapshot.clock,
+            active,
+        }
+    }
+
+    /// Latest known state of the game, without waiting on the game's lock.
+    pub fn state(&self) -> GameState {
+        self.snapshot.borrow().game.clone()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn publish(&self, event: GameEvent) {
+        // An error only means that nobody is listening.
+        let _ = self.events.send(event);
+    }
+
+    /// Span of the operations on the game.
+    pub fn span(&self) -> Span {
+        info_span!("game", id = self.info.id, owner = %self.info.owner)
+    }
+}
+
+fn convert_cell_id(id: &str) -> Position {
+    let (row, column) = id.split_at(id.len() / 2);
+    (row.parse().unwrap(), column.parse().unwrap())
+}
+
+/// A sequence of moves written as bots print it.
+fn format_moves(moves: &[Move]) -> String {
+    let cell = |(row, column): Position| {
+        if row < 10 && column < 10 {
+            format!("{row}{column}")
+        } else {
+            format!("{row:02}{column:02}")
+        }
+    };
+
+    moves
+        .iter()
+        .map(|m| format!("{},{};", cell(m.from), cell(m.to)))
+        .collect()
 }
 
 impl Game {
+    pub fn new(checkers: GameState) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let (snapshot, _) = watch::channel(Snapshot {
+            game: checkers.clone(),
+            history: vec![],
+            clock: None,
+        });
+
+        Self {
+            checkers,
+            history: vec![],
+            clock: None,
+            events,
+            snapshot,
+        }
+    }
+
+    fn publish(&self, event: GameEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Applies a sequence for the current player and notifies subscribers.
+    fn apply(&mut self, seq: &[Move]) -> Result<(), Error> {
+        let now = now_ms();
+        if self.check_time(now) {
+            return Err(Error::OutOfTime);
+        }
+        if !matches!(self.checkers.status, GameStatus::Running) {
+            return Err(Error::InvalidAction);
+        }
+
+        let player = self.checkers.current_player;
+        let captures = self
+            .checkers
+            .list_valid_moves()
+            .into_iter()
+            .find(|m| m.0 == seq)
+            .map(|m| m.1);
+
+        self.checkers.apply_sequence(seq)?;
+        if let Some(clock) = &mut self.clock {
+            clock.press(player, now);
+        }
+
+        let turn = Turn {
+            player,
+            moves: seq.to_vec(),
+            captures: captures.unwrap_or_default(),
+            action: None,
+        };
+        self.history.push(turn.clone());
+        self.publish(GameEvent::Move(turn));
+        self.publish_state();
+
+        Ok(())
+    }
+
+    /// Notifies subscribers of the new state, once the clock has been stopped
+    /// if the game is over.
+    pub fn publish_state(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            if !matches!(self.checkers.status, GameStatus::Running) {
+                clock.stop(self.checkers.current_player, now_ms());
+            }
+        }
+
+        self.snapshot.send_replace(Snapshot {
+            game: self.checkers.clone(),
+            history: self.history.clone(),
+            clock: self.clock.clone(),
+        });
+        self.publish(GameEvent::State {
+            game: self.checkers.clone(),
+        });
+        if let Some(clock) = &self.clock {
+            self.publish(GameEvent::Clock {
+                clock: clock.clone(),
+            });
+        }
+    }
+
+    /// Ends the game if the current player has run out of time.
+    fn check_time(&mut self, now: u64) -> bool {
+        let player = self.checkers.current_player;
+        let out_of_time = matches!(self.checkers.status, GameStatus::Running)
+            && self
+                .clock
+                .as_ref()
+                .is_some_and(|c| c.is_running() && c.remaining(player, player, now) == 0);
+
+        if out_of_time {
+            self.out_of_time(player);
+        }
+
+        out_of_time
+    }
+
+    fn out_of_time(&mut self, player: Player) {
+        self.checkers.status = GameStatus::VictoryOnTime(player.opponent());
+        self.publish_state();
+    }
+
+    /// Restarts the clock of the current player, so that an AI is not charged
+    /// for the time spent waiting for a worker.
+    fn restart_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.restart(now_ms());
+        }
+    }
+
     pub async fn play_ai(&mut self, submission: Submission) -> Result<String, Error> {
-        let mut child = submission.start().await?;
+        self.restart_clock();
+
+        let lang = submission.lang;
+        let span = info_span!(
+            "bot",
+            owner = submission.name,
+            version = submission.version,
+            lang = %lang,
+        );
+        let start = Instant::now();
+
+        let result = self.run_ai(submission).instrument(span.clone()).await;
+        metrics::record_bot_run(lang, start.elapsed(), &result);
+
+        span.in_scope(|| {
+            let duration_ms = start.elapsed().as_millis() as u64;
+            match &result {
+                Ok(_) => debug!(duration_ms, "bot played"),
+                Err(e) => info!(duration_ms, error = ?e, "bot failed"),
+            }
+        });
 
-        let mut stdin = child.stdin.take().unwrap();
-        let mut stdout = BufReader::new(child.stdout.take().unwrap());
-        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        result
+    }
+
+    async fn run_ai(&mut self, submission: Submission) -> Result<String, Error> {
+        let mut bot = submission.start().await?;
+
+        let mut stdin = bot.child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(bot.child.stdout.take().unwrap());
+        let mut stderr = BufReader::new(bot.child.stderr.take().unwrap());
 
         stdin
             .write_all(format!("{}\n", self.checkers.current_player).as_bytes())
             .await
             .map_err(Error::from)?;
 
+        let variant = self.checkers.variant;
+        if variant != Variant::International {
+            stdin
+                .write_all(format!("{VARIANT_MESSAGE} {variant}\n").as_bytes())
+                .await
+                .map_err(Error::from)?;
+        }
+
         stdin
             .write_all(self.checkers.to_csv_string().as_bytes())
             .await
             .map_err(Error::from)?;
 
-        child.status().await?;
+        let player = self.checkers.current_player;
+        let now = now_ms();
+        let time_left = self.clock.as_ref().map(|c| {
+            (
+                c.remaining(player, player, now),
+                c.remaining(player.opponent(), player, now),
+            )
+        });
+        if let Some((own, opponent)) = time_left {
+            stdin
+                .write_all(format!("{TIME_MESSAGE} {own} {opponent}\n").as_bytes())
+                .await
+                .map_err(Error::from)?;
+        }
+
+        for entry in book().moves(&self.checkers) {
+            let moves = format_moves(&entry.moves);
+            stdin
+                .write_all(format!("{BOOK_MESSAGE} {moves} {}\n", entry.weight).as_bytes())
+                .await
+                .map_err(Error::from)?;
+        }
+
+        let draw_offered = self.draw_offered();
+        if draw_offered {
+            stdin
+                .write_all(format!("{DRAW_MESSAGE}\n").as_bytes())
+                .await
+                .map_err(Error::from)?;
+        }
+        drop(stdin);
+
+        // The clock of the AI only runs once its code is compiled. Compiler
+        // errors end up in its output.
+        let mut compile_output = String::new();
+        let started = timeout(START_TIMEOUT, async {
+            let mut chunk = String::new();
+            while stderr.read_line(&mut chunk).await? > 0 {
+                if chunk.trim_end() == READY_MESSAGE {
+                    return Ok(());
+                }
+                self.publish(GameEvent::AiOutput {
+                    output: chunk.clone(),
+                });
+                compile_output.push_str(&chunk);
+                chunk.clear();
+            }
+            Ok::<_, Error>(())
+        })
+        .await;
+        match started {
+            Ok(result) => result?,
+            Err(_) => {
+                bot.kill().await;
+                return Err(Error::AIFailed {
+                    error: super::AIError::Timeout,
+                    ai_output: compile_output,
+                    move_: None,
+                });
+            }
+        }
+        self.restart_clock();
+
+        // stderr is forwarded line by line while the AI runs, and both pipes
+        // are drained concurrently so a chatty AI cannot block on a full pipe.
+        let read_stderr = async {
+            let mut ai_output = String::new();
+            let mut chunk = String::new();
+
+            while stderr.read_line(&mut chunk).await? > 0 {
+                self.publish(GameEvent::AiOutput {
+                    output: chunk.clone(),
+                });
+                ai_output.push_str(&chunk);
+                chunk.clear();
+            }
+
+            Ok::<_, Error>(ai_output)
+        };
 
         let mut line = String::new();
-        stdout.read_line(&mut line).await?;
+        let read = async { join!(stdout.read_line(&mut line), read_stderr) };
+
+        // The AI is stopped as soon as its time runs out.
+        let (stdout_result, ai_output) = match time_left {
+            Some((own, _)) => match timeout(Duration::from_millis(own), read).await {
+                Ok(result) => result,
+                Err(_) => {
+                    bot.kill().await;
+                    self.out_of_time(player);
+                    return Err(Error::AIFailed {
+                        error: super::AIError::Timeout,
+                        ai_output: String::new(),
+                        move_: None,
+                    });
+                }
+            },
+            None => read.await,
+        };
+        stdout_result?;
+        let ai_output = compile_output + &ai_output?;
         let line = line.trim();
 
-        let mut ai_output = String::new();
-        stderr.read_to_string(&mut ai_output).await?;
+        if bot.child.status().await?.code() == Some(DOCKER_ERROR_CODE) {
+            return Err(Error::InfrastructureFailure);
+        }
+
+        if draw_offered && line == DRAW_MESSAGE {
+            self.act(player, Action::AcceptDraw)?;
+            return Ok(ai_output);
+        }
 
         if !AI_OUTPUT_REGEX.is_match(line) {
+            self.forfeit(player);
             return Err(Error::AIFailed {
                 error: super::AIError::InvalidOutput,
                 ai_output,
@@ -61,125 +680,794 @@ impl Game {
             .split(";")
             .filter(|m| !m.is_empty())
             .map(|m| {
-                let chars = m.chars().collect::<Vec<_>>();
+                let (from, to) = m.split_once(',').unwrap();
                 Move {
-                    from: convert_cell_id(&chars[0..=1]),
-                    to: convert_cell_id(&chars[3..=4]),
+                    from: convert_cell_id(from),
+                    to: convert_cell_id(to),
                 }
             })
             .collect::<Vec<_>>();
 
-        if let Err(Error::InvalidMove) = self.checkers.apply_sequence(&seq) {
-            self.checkers.status = GameStatus::Victory(self.human_player);
-            return Err(Error::AIFailed {
-                error: super::AIError::InvalidMove,
+        if draw_offered {
+            self.act(player, Action::DeclineDraw)?;
+        }
+
+        match self.apply(&seq) {
+            Err(Error::InvalidMove { .. }) => {
+                self.forfeit(player);
+                Err(Error::AIFailed {
+                    error: super::AIError::InvalidMove,
+                    ai_output,
+                    move_: Some(seq),
+                })
+            }
+            Err(Error::OutOfTime) => Err(Error::AIFailed {
+                error: super::AIError::Timeout,
                 ai_output,
                 move_: Some(seq),
-            });
+            }),
+            _ => Ok(ai_output),
+        }
+    }
+
+    /// Plays the turn of the built-in engine, which forfeits if it cannot move
+    /// and only accepts draws when it does not expect to win.
+    pub async fn play_engine(&mut self) -> Result<String, Error> {
+        self.restart_clock();
+
+        let player = self.checkers.current_player;
+        let checkers = self.checkers.clone();
+        let draw_offered = self.draw_offered();
+        let best = spawn_blocking(move || {
+            // The moves of the book are not scored, so a draw offer is only
+            // answered after a search.
+            if draw_offered {
+                engine::search(&checkers, engine::DEPTH)
+            } else {
+                engine::play(&checkers)
+            }
+        })
+        .await
+        .map_err(|_| Error::InfrastructureFailure)?;
+
+        if draw_offered {
+            if best.as_ref().is_none_or(|(_, score)| *score <= 0) {
+                return self.act(player, Action::AcceptDraw).map(|_| String::new());
+            }
+            self.act(player, Action::DeclineDraw)?;
+        }
+
+        match best {
+            Some((moves, _)) => self.apply(&moves)?,
+            None => self.forfeit(player),
         }
 
-        Ok(ai_output)
+        Ok(String::new())
     }
 
     pub async fn play_human(&mut self, moves: Vec<Move>) -> Result<(), Error> {
-        self.checkers.apply_sequence(&moves)
+        self.apply(&moves)
+    }
+
+    /// Ends the game with a victory of the opponent of the given player.
+    pub fn forfeit(&mut self, player: Player) {
+        self.checkers.status = GameStatus::Victory(player.opponent());
+        self.publish_state();
+    }
+
+    /// The offer or request that has not been answered yet, along with the
+    /// player who made it. Any entry of the opponent after it answers it.
+    pub fn pending(&self) -> Option<(Player, Action)> {
+        let mut answered = [false, false];
+
+        for turn in self.history.iter().rev() {
+            let opponent = turn.player.opponent() as usize;
+            match turn.action {
+                Some(action @ (Action::OfferDraw | Action::RequestTakeback))
+                    if !answered[opponent] =>
+                {
+                    return Some((turn.player, action))
+                }
+                _ => answered[turn.player as usize] = true,
+            }
+
+            if answered == [true, true] {
+                break;
+            }
+        }
+
+        None
+    }
+
+    fn record(&mut self, player: Player, action: Action) {
+        let turn = Turn {
+            player,
+            moves: vec![],
+            captures: vec![],
+            action: Some(action),
+        };
+        self.history.push(turn.clone());
+        self.publish(GameEvent::Action(turn));
+    }
+
+    /// Plays an action for the given player, who must be in a running game.
+    pub fn act(&mut self, player: Player, action: Action) -> Result<(), Error> {
+        if self.check_time(now_ms()) {
+            return Err(Error::OutOfTime);
+        }
+        if !matches!(self.checkers.status, GameStatus::Running) {
+            return Err(Error::InvalidAction);
+        }
+
+        let offered = self
+            .pending()
+            .filter(|(by, _)| *by == player.opponent())
+            .map(|(_, offer)| offer);
+
+        match (action, offered) {
+            (Action::Resign, _) => {
+                self.record(player, action);
+                self.checkers.status = GameStatus::Victory(player.opponent());
+            }
+            (Action::OfferDraw | Action::AcceptDraw, Some(Action::OfferDraw)) => {
+                self.record(player, Action::AcceptDraw);
+                self.checkers.status = GameStatus::Draw;
+            }
+            (Action::OfferDraw, _) => self.record(player, action),
+            (Action::RequestTakeback | Action::AcceptTakeback, Some(Action::RequestTakeback)) => {
+                self.take_back(player.opponent())?;
+                self.record(player, Action::AcceptTakeback);
+            }
+            (Action::RequestTakeback, _) => {
+                if !self
+                    .history
+                    .iter()
+                    .any(|t| t.player == player && t.action.is_none())
+                {
+                    return Err(Error::InvalidAction);
+                }
+                self.record(player, action);
+            }
+            (Action::DeclineDraw, Some(Action::OfferDraw))
+            | (Action::DeclineTakeback, Some(Action::RequestTakeback)) => {
+                self.record(player, action)
+            }
+            _ => return Err(Error::InvalidAction),
+        }
+
+        self.publish_state();
+
+        Ok(())
+    }
+
+    /// Undoes every move since the last one of the given player, included, by
+    /// replaying the rest of the history from the start.
+    fn take_back(&mut self, player: Player) -> Result<(), Error> {
+        let Some(last) = self
+            .history
+            .iter()
+            .rposition(|t| t.player == player && t.action.is_none())
+        else {
+            return Err(Error::InvalidAction);
+        };
+
+        let mut index = 0;
+        self.history.retain(|t| {
+            index += 1;
+            index <= last || t.action.is_some()
+        });
+
+        let mut checkers = GameState::new(self.checkers.variant);
+        for turn in self.history.iter().filter(|t| t.action.is_none()) {
+            checkers.apply_sequence(&turn.moves)?;
+        }
+
+        if let Some(clock) = &mut self.clock {
+            clock.switch(self.checkers.current_player, now_ms());
+        }
+        self.checkers = checkers;
+
+        Ok(())
+    }
+
+    /// Whether the opponent of the current player offers a draw.
+    fn draw_offered(&self) -> bool {
+        self.pending() == Some((self.checkers.current_player.opponent(), Action::OfferDraw))
     }
 }
 
-#[get("/game")]
-pub async fn get_game(state: &AppState, user: User) -> Result<Json<GameState>, Error> {
-    let game = {
-        let mutex = {
+/// The submission playing the given color of the game, if it is a bot: the
+/// active one, or the version entered in the contest of the game.
+pub fn bot_submission(
+    state: &SharedState,
+    info: &GameInfo,
+    player: Player,
+) -> Result<Submission, Error> {
+    match info.side(player) {
+        Side::Bot(name) => {
             let lock = state.lock()?;
-            lock.games.get(&user.name).ok_or(Error::NotFound)?.clone()
-        };
+            match &info.contest {
+                Some(contest) => lock.entry_submission(contest, name),
+                None => lock.submissions.get(name).cloned(),
+            }
+            .ok_or(Error::NotFound)
+        }
+        Side::Human(_) | Side::Engine => Err(Error::NotFound),
+    }
+}
+
+/// The given game of the user, or their most recent one.
+fn user_game(state: &State, user: &User, id: Option<u64>) -> Result<Arc<GameHandle>, Error> {
+    let mut games = state
+        .games
+        .values()
+        .filter(|g| g.info.player_of(user.owner()).is_some());
 
-        let lock = mutex.lock().await;
-        lock.checkers.clone()
+    let game = match id {
+        Some(id) => games.find(|g| g.info.id == id),
+        None => games.max_by_key(|g| g.info.id),
     };
 
-    Ok(Json(game))
+    game.cloned().ok_or(Error::NotFound)
 }
 
-#[post("/game/start?<is_first_player>")]
-pub async fn start(
+/// The user or team whose games are played as `name`, other than `owner`.
+fn opponent_owner(state: &State, owner: &str, name: Option<&str>) -> Result<String, Error> {
+    let name = name.ok_or(Error::NotFound)?;
+    let opponent = match state.users.get(name) {
+        Some(user) => user.team.clone().unwrap_or_else(|| name.to_owned()),
+        None if state.teams.contains_key(name) => name.to_owned(),
+        None => return Err(Error::NotFound),
+    };
+
+    if opponent == owner {
+        Err(Error::NotFound)
+    } else {
+        Ok(opponent)
+    }
+}
+
+/// The user's game, or their most recent one if no `id` is given.
+#[utoipa::path(
+    tag = "game",
+    responses((status = 200, body = GameState)),
+    security(("session" = [])),
+)]
+#[get("/game?<id>")]
+pub async fn get_game(
     state: &AppState,
     user: User,
-    is_first_player: bool,
-) -> Result<Json<TurnStatus>, Error> {
-    let checkers: GameState = Default::default();
+    id: Option<u64>,
+) -> Result<Json<GameState>, Error> {
+    Ok(Json(user_game(&*state.lock()?, &user, id)?.state()))
+}
 
-    let mut game = Game {
-        human_player: if is_first_player {
-            Player::White
-        } else {
-            Player::Black
-        },
-        checkers: checkers.clone(),
+/// Streams the user's game as Server-Sent Events. The current state is sent
+/// first, followed by every `GameEvent` as it happens.
+#[utoipa::path(
+    tag = "game",
+    responses((status = 200, description = "Server-Sent Events, each holding a `GameEvent`", content_type = "text/event-stream", body = GameEvent)),
+    security(("session" = [])),
+)]
+#[get("/game/events?<id>")]
+pub async fn events(
+    state: &AppState,
+    user: User,
+    id: Option<u64>,
+    shutdown: Shutdown,
+) -> Result<EventStream![], Error> {
+    let handle = user_game(&*state.lock()?, &user, id)?;
+    Ok(event_stream(&handle, shutdown))
+}
+
+/// A legal sequence of moves along with the pieces it captures.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LegalMove {
+    moves: Vec<Move>,
+    /// `[row, column]` of the captured pieces.
+    #[schema(value_type = Vec<Vec<usize>>)]
+    captures: Vec<Position>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Hint {
+    /// Every sequence of moves the user may play.
+    moves: Vec<LegalMove>,
+    /// Whether capturing is mandatory, in which case every sequence captures.
+    mandatory_capture: bool,
+    /// The sequence the built-in engine would play, if asked for.
+    best: Option<Vec<Move>>,
+}
+
+/// Lists the sequences of moves the user may play in their game, and suggests
+/// the engine's if `best` is set.
+#[utoipa::path(
+    tag = "game",
+    responses((status = 200, body = Hint)),
+    security(("session" = [])),
+)]
+#[get("/game/hint?<id>&<best>")]
+pub async fn hint(
+    state: &AppState,
+    user: User,
+    id: Option<u64>,
+    best: Option<bool>,
+) -> Result<Json<Hint>, Error> {
+    let handle = user_game(&*state.lock()?, &user, id)?;
+    if !handle.info.hints {
+        return Err(Error::HintsDisabled);
+    }
+
+    let checkers = handle.state();
+    if !matches!(checkers.status, GameStatus::Running) {
+        return Err(Error::InvalidAction);
+    }
+    if handle.info.player_of(user.owner()) != Some(checkers.current_player) {
+        return Err(Error::NotYourTurn);
+    }
+
+    let moves = checkers
+        .list_valid_moves()
+        .into_iter()
+        .map(|(moves, captures)| LegalMove { moves, captures })
+        .collect::<Vec<_>>();
+    let mandatory_capture = moves.iter().any(|m| !m.captures.is_empty());
+
+    let best = if best.unwrap_or(false) {
+        spawn_blocking(move || engine::search(&checkers, engine::DEPTH))
+            .instrument(handle.span())
+            .await
+            .map_err(|_| Error::InfrastructureFailure)?
+            .map(|(moves, _)| moves)
+    } else {
+        None
     };
 
-    let mut ai_output = String::new();
-    if !is_first_player {
-        let submission = state
-            .lock()
-            .unwrap()
-            .submissions
-            .get(&user.name)
-            .ok_or(Error::NotFound)?
-            .clone();
+    Ok(Json(Hint {
+        moves,
+        mandatory_capture,
+        best,
+    }))
+}
+
+/// Ends the games of the users who have run out of time. Bots are stopped by
+/// their own timeout instead.
+pub async fn watch_clocks(state: SharedState) {
+    loop {
+        sleep(CLOCK_TICK).await;
+
+        let now = now_ms();
+        let flagged = match state.lock() {
+            Ok(lock) => lock
+                .games
+                .values()
+                .filter(|g| {
+                    let snapshot = g.snapshot.borrow();
+                    let player = snapshot.game.current_player;
 
-        ai_output = game.play_ai(submission).await?;
+                    matches!(snapshot.game.status, GameStatus::Running)
+                        && !g.info.side(player).is_ai()
+                        && snapshot.clock.as_ref().is_some_and(|c| {
+                            c.is_running() && c.remaining(player, player, now) == 0
+                        })
+                })
+                .cloned()
+                .collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+
+        for handle in flagged {
+            // A game being played checks the time itself.
+            let Ok(mut game) = handle.game.try_lock() else {
+                continue;
+            };
+            if game.check_time(now_ms()) {
+                drop(game);
+                // Failures are logged when converted to an `Error`.
+                if let Ok(lock) = state.lock() {
+                    let _ = lock.save_game(&handle);
+                }
+            }
+        }
     }
+}
 
-    let checkers = game.checkers.clone();
+pub fn event_stream(handle: &GameHandle, mut shutdown: Shutdown) -> EventStream![] {
+    let mut events = handle.subscribe();
+    let initial = handle.state();
 
-    let mut lock = state.lock().unwrap();
-    lock.games.insert(user.name, Arc::new(Mutex::new(game)));
+    EventStream! {
+        yield Event::json(&GameEvent::State { game: initial });
 
-    Ok(Json(TurnStatus {
-        game: checkers,
+        loop {
+            let event = select! {
+                event = events.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            let stopped = matches!(event, GameEvent::Stopped);
+            yield Event::json(&event);
+
+            if stopped {
+                break;
+            }
+        }
+    }
+}
+
+/// The game as returned by the endpoints that play a turn.
+fn turn_status(handle: &GameHandle, ai_output: String, job: Option<u64>) -> Json<TurnStatus> {
+    Json(TurnStatus {
+        id: handle.info.id,
+        game: handle.state(),
+        clock: handle.snapshot().clock,
         ai_output,
-    }))
+        job,
+    })
+}
+
+/// Plays the turn of the AI if it is its turn, waiting for it unless `wait`
+/// is false.
+async fn ai_turn(
+    state: &AppState,
+    handle: &Arc<GameHandle>,
+    wait: bool,
+) -> Result<Json<TurnStatus>, Error> {
+    let current = handle.state();
+    if !matches!(current.status, GameStatus::Running)
+        || !handle.info.side(current.current_player).is_ai()
+    {
+        return Ok(turn_status(handle, String::new(), None));
+    }
+
+    let (job, result) = jobs::queue_bot_move(state, handle)?;
+    debug!(job, "bot turn queued");
+
+    let ai_output = if wait {
+        result.await.map_err(|_| Error::InfrastructureFailure)??
+    } else {
+        String::new()
+    };
+
+    Ok(turn_status(handle, ai_output, Some(job)))
+}
+
+/// Starts a game against the user's submission, the built-in engine or
+/// another user. Only one game at a time may be played against each
+/// opponent: a finished one is replaced.
+#[utoipa::path(
+    tag = "game",
+    params(("opponent" = Option<Opponent>, Query), SetupParams),
+    responses((status = 200, body = TurnStatus)),
+    security(("session" = [])),
+)]
+#[post("/game/start?<is_first_player>&<wait>&<opponent>&<name>&<setup..>")]
+pub async fn start(
+    state: &AppState,
+    user: User,
+    is_first_player: bool,
+    wait: Option<bool>,
+    opponent: Option<Opponent>,
+    name: Option<&str>,
+    setup: SetupParams,
+) -> Result<Json<TurnStatus>, Error> {
+    let time_control = setup.time_control()?;
+    let variant = setup.variant.unwrap_or_default();
+    let owner = user.owner().to_owned();
+    let handle = {
+        let mut lock = state.lock()?;
+
+        let human = Side::Human(owner.clone());
+        let other = match opponent.unwrap_or_default() {
+            Opponent::Bot => Side::Bot(owner.clone()),
+            Opponent::Engine => Side::Engine,
+            Opponent::User => Side::Human(opponent_owner(&lock, &owner, name)?),
+        };
+
+        let previous = lock
+            .games
+            .values()
+            .find(|g| g.info.opposes(&human, &other))
+            .cloned();
+        if let Some(previous) = previous {
+            if matches!(previous.state().status, GameStatus::Running) {
+                return Err(Error::GameAlreadyInProgress {
+                    game: previous.info.id,
+                });
+            }
+            lock.games.remove(&previous.info.id);
+            lock.retire_game(previous);
+        }
+
+        let against_user = matches!(other, Side::Human(_));
+        if against_user && setup.hints == Some(true) {
+            return Err(Error::HintsDisabled);
+        }
+        let hints = !against_user && setup.hints.unwrap_or(true);
+        let (white, black) = if is_first_player == (variant.rules().first_player == Player::White) {
+            (human, other)
+        } else {
+            (other, human)
+        };
+        let info = GameInfo {
+            id: lock.new_game_id(),
+            owner,
+            white,
+            black,
+            contest: None,
+            hints,
+        };
+        let mut game = Game::new(GameState::new(variant));
+        if let Some(control) = time_control {
+            game.clock = Some(Clock::new(control, now_ms()));
+            game.publish_state();
+        }
+        let handle = Arc::new(GameHandle::new(info, game));
+
+        lock.games.insert(handle.info.id, handle.clone());
+        lock.save_game(&handle)?;
+        handle
+    };
+    let span = handle.span();
+    span.in_scope(
+        || info!(%variant, white = ?handle.info.white, black = ?handle.info.black, "game started"),
+    );
+
+    ai_turn(state, &handle, wait.unwrap_or(true))
+        .instrument(span)
+        .await
 }
 
-#[post("/game", format = "json", data = "<moves>")]
+#[utoipa::path(
+    tag = "game",
+    request_body = Vec<Move>,
+    responses((status = 200, body = TurnStatus)),
+    security(("session" = [])),
+)]
+#[post("/game?<id>&<wait>", format = "json", data = "<moves>")]
 pub async fn play(
     state: &AppState,
     user: User,
     moves: Json<Vec<Move>>,
+    id: Option<u64>,
+    wait: Option<bool>,
 ) -> Result<Json<TurnStatus>, Error> {
-    let submission = state
-        .lock()
-        .unwrap()
-        .submissions
-        .get(&user.name)
-        .ok_or(Error::NotFound)?
-        .clone();
-    let game = state.lock().unwrap().games.get(&user.name).map(Arc::clone);
+    let handle = user_game(&*state.lock()?, &user, id)?;
+
+    async {
+        {
+            let mut game = handle.game.lock().await;
+            if !matches!(
+                handle.info.side(game.checkers.current_player),
+                Side::Human(name) if name == user.owner()
+            ) {
+                return Err(Error::NotYourTurn);
+            }
 
-    if game.is_none() {
-        return Err(Error::NotFound);
+            game.play_human(moves.into_inner()).await?;
+        }
+        state.lock()?.save_game(&handle)?;
+
+        ai_turn(state, &handle, wait.unwrap_or(true)).await
     }
+    .instrument(handle.span())
+    .await
+}
 
-    let game = game.unwrap();
-    let mut lock = game.lock().await;
+/// Resigns, offers, answers or requests something in the user's game. An AI
+/// answers takebacks immediately (see `AI_TAKEBACKS`), and draw offers on its
+/// next turn.
+#[utoipa::path(
+    tag = "game",
+    request_body = Action,
+    responses((status = 200, body = TurnStatus)),
+    security(("session" = [])),
+)]
+#[post("/game/action?<id>", format = "json", data = "<action>")]
+pub async fn act(
+    state: &AppState,
+    user: User,
+    action: Json<Action>,
+    id: Option<u64>,
+) -> Result<Json<TurnStatus>, Error> {
+    let handle = user_game(&*state.lock()?, &user, id)?;
+    let player = handle.info.player_of(user.owner()).ok_or(Error::NotFound)?;
+    let action = action.into_inner();
 
-    lock.play_human(moves.into_inner()).await?;
-    let output = lock.play_ai(submission).await?;
+    async {
+        {
+            let mut game = handle.game.lock().await;
+            game.act(player, action)?;
+            info!(?player, ?action, "action played");
 
-    Ok(Json(TurnStatus {
-        game: lock.checkers.clone(),
-        ai_output: output,
-    }))
+            if game.pending() == Some((player, Action::RequestTakeback))
+                && handle.info.side(player.opponent()).is_ai()
+            {
+                let answer = if config().ai_takebacks {
+                    Action::AcceptTakeback
+                } else {
+                    Action::DeclineTakeback
+                };
+                game.act(player.opponent(), answer)?;
+            }
+        }
+        state.lock()?.save_game(&handle)?;
+
+        Ok(turn_status(&handle, String::new(), None))
+    }
+    .instrument(handle.span())
+    .await
+}
+
+#[utoipa::path(
+    tag = "game",
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/game/stop?<id>")]
+pub async fn stop(state: &AppState, user: User, id: Option<u64>) -> Result<(), Error> {
+    let mut lock = state.lock()?;
+    let game = user_game(&lock, &user, id)?;
+    game.span().in_scope(|| info!("game stopped"));
+    lock.games.remove(&game.info.id);
+    lock.retire_game(game);
+
+    Ok(())
 }
 
-#[post("/game/stop")]
-pub async fn stop(state: &AppState, user: User) -> Result<(), Error> {
-    let game = state.lock().unwrap().games.remove(&user.name);
+#[cfg(test)]
+mod test {
+    use super::{
+        convert_cell_id, format_moves, Action, Error, Game, GameRecord, Side, AI_OUTPUT_REGEX,
+    };
+    use crate::{
+        clock::{now_ms, Clock, ClockKind, TimeControl},
+        game::{GameState, GameStatus, Move, Player},
+    };
 
-    if game.is_none() {
-        return Err(Error::NotFound);
+    fn m(x1: usize, y1: usize, x2: usize, y2: usize) -> Vec<Move> {
+        vec![Move {
+            from: (x1, y1),
+            to: (x2, y2),
+        }]
     }
 
-    Ok(())
+    #[test]
+    fn reads_games_saved_before_sides() {
+        let board = serde_json::to_value(GameState::default().board).unwrap();
+        let record = serde_json::json!({
+            "id": 3,
+            "owner": "alice",
+            "human_player": "black",
+            "contest": null,
+            "game": { "board": board, "current_player": "white", "status": { "status": "running" } },
+            "history": [{ "player": "white", "moves": m(6, 1, 5, 0), "captures": [] }],
+            "active": true,
+        });
+
+        let record: GameRecord = serde_json::from_value(record).unwrap();
+        assert_eq!(record.info.white, Side::Bot("alice".to_owned()));
+        assert_eq!(record.info.black, Side::Human("alice".to_owned()));
+        assert_eq!(record.history.len(), 1);
+
+        let saved = serde_json::to_value(&record).unwrap();
+        let record: GameRecord = serde_json::from_value(saved).unwrap();
+        assert_eq!(record.info.black, Side::Human("alice".to_owned()));
+        assert!(record.info.hints);
+    }
+
+    #[test]
+    fn draw_offer_stands_until_answered() {
+        let mut game = Game::new(GameState::default());
+
+        game.act(Player::White, Action::OfferDraw).unwrap();
+        game.apply(&m(6, 1, 5, 0)).unwrap();
+        assert_eq!(game.pending(), Some((Player::White, Action::OfferDraw)));
+        assert!(game.draw_offered());
+
+        game.act(Player::Black, Action::OfferDraw).unwrap();
+        assert!(matches!(game.checkers.status, GameStatus::Draw));
+        assert_eq!(
+            game.history.last().unwrap().action,
+            Some(Action::AcceptDraw)
+        );
+        assert!(game.act(Player::White, Action::Resign).is_err());
+    }
+
+    #[test]
+    fn move_answers_offer() {
+        let mut game = Game::new(GameState::default());
+
+        game.apply(&m(6, 1, 5, 0)).unwrap();
+        game.act(Player::White, Action::OfferDraw).unwrap();
+        game.apply(&m(3, 0, 4, 1)).unwrap();
+
+        assert_eq!(game.pending(), None);
+        assert!(game.act(Player::White, Action::AcceptDraw).is_err());
+    }
+
+    #[test]
+    fn takeback_undoes_the_reply() {
+        let mut game = Game::new(GameState::default());
+
+        assert!(game.act(Player::White, Action::RequestTakeback).is_err());
+
+        game.apply(&m(6, 1, 5, 0)).unwrap();
+        game.apply(&m(3, 0, 4, 1)).unwrap();
+        game.act(Player::White, Action::RequestTakeback).unwrap();
+        game.act(Player::Black, Action::AcceptTakeback).unwrap();
+
+        assert_eq!(game.checkers.current_player, Player::White);
+        assert_eq!(
+            game.checkers.to_csv_string(),
+            GameState::default().to_csv_string()
+        );
+        assert_eq!(
+            game.history.iter().map(|t| t.action).collect::<Vec<_>>(),
+            vec![Some(Action::RequestTakeback), Some(Action::AcceptTakeback)]
+        );
+    }
+
+    #[test]
+    fn resign_ends_the_game() {
+        let mut game = Game::new(GameState::default());
+
+        game.act(Player::Black, Action::Resign).unwrap();
+
+        assert!(matches!(
+            game.checkers.status,
+            GameStatus::Victory(Player::White)
+        ));
+        assert!(game.apply(&m(6, 1, 5, 0)).is_err());
+    }
+
+    #[test]
+    fn flag_fall_ends_the_game() {
+        let mut game = Game::new(GameState::default());
+        let control = TimeControl {
+            kind: ClockKind::Fischer,
+            initial_ms: 1_000,
+            increment_ms: 0,
+        };
+        game.clock = Some(Clock::new(control, now_ms() - 2_000));
+
+        assert!(matches!(game.apply(&m(6, 1, 5, 0)), Err(Error::OutOfTime)));
+        assert!(matches!(
+            game.checkers.status,
+            GameStatus::VictoryOnTime(Player::Black)
+        ));
+        assert!(!game.clock.unwrap().is_running());
+    }
+
+    #[test]
+    fn parses_one_or_two_digit_coordinates() {
+        assert!(AI_OUTPUT_REGEX.is_match("61,50;"));
+        assert!(AI_OUTPUT_REGEX.is_match("0710,0611;0611,0409;"));
+        assert!(!AI_OUTPUT_REGEX.is_match("710,611;"));
+
+        assert_eq!(convert_cell_id("61"), (6, 1));
+        assert_eq!(convert_cell_id("1011"), (10, 11));
+    }
+
+    #[test]
+    fn formats_moves_as_bots_print_them() {
+        assert_eq!(format_moves(&m(6, 1, 5, 0)), "61,50;");
+
+        let moves = [
+            Move {
+                from: (7, 10),
+                to: (5, 8),
+            },
+            Move {
+                from: (5, 8),
+                to: (3, 6),
+            },
+        ];
+        assert_eq!(format_moves(&moves), "0710,58;58,36;");
+        assert!(AI_OUTPUT_REGEX.is_match(&format_moves(&moves)));
+    }
 }
@@ -0,0 +1,126 @@
+use super::{
+    play::{event_stream, GameEvent, GameHandle, Side, Turn},
+    AppState, Error,
+};
+use crate::{
+    clock::Clock,
+    game::{GameState, GameStatus, Player},
+};
+use rocket::{get, response::stream::EventStream, serde::json::Json, FromFormField, Shutdown};
+use serde::Serialize;
+use std::{cmp::Reverse, sync::Arc};
+use utoipa::ToSchema;
+
+#[derive(FromFormField, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum StatusFilter {
+    Running,
+    Finished,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GameSummary {
+    id: u64,
+    owner: String,
+    white: Side,
+    black: Side,
+    contest: Option<String>,
+    status: GameStatus,
+    current_player: Player,
+    /// Number of sequences of moves played, actions excluded.
+    turns: usize,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GameDetails {
+    #[serde(flatten)]
+    summary: GameSummary,
+    game: GameState,
+    history: Vec<Turn>,
+    clock: Option<Clock>,
+}
+
+fn summarize(handle: &GameHandle) -> GameSummary {
+    let snapshot = handle.snapshot();
+
+    GameSummary {
+        id: handle.info.id,
+        owner: handle.info.owner.clone(),
+        white: handle.info.white.clone(),
+        black: handle.info.black.clone(),
+        contest: handle.info.contest.clone(),
+        status: snapshot.game.status,
+        current_player: snapshot.game.current_player,
+        turns: snapshot
+            .history
+            .iter()
+            .filter(|t| t.action.is_none())
+            .count(),
+    }
+}
+
+pub fn find_game(state: &AppState, id: u64) -> Result<Arc<GameHandle>, Error> {
+    state.lock()?.find_game(id)?.ok_or(Error::NotFound)
+}
+
+/// Lists every game, most recent first.
+#[utoipa::path(
+    tag = "spectate",
+    params(("status" = Option<StatusFilter>, Query)),
+    responses((status = 200, body = Vec<GameSummary>)),
+)]
+#[get("/games?<player>&<contest>&<status>")]
+pub async fn list_games(
+    state: &AppState,
+    player: Option<&str>,
+    contest: Option<&str>,
+    status: Option<StatusFilter>,
+) -> Result<Json<Vec<GameSummary>>, Error> {
+    let running = status.map(|s| s == StatusFilter::Running);
+
+    let mut games = state
+        .lock()?
+        .all_games()
+        .filter(|g| player.is_none_or(|p| g.info.involves(p)))
+        .filter(|g| contest.is_none_or(|c| g.info.contest.as_deref() == Some(c)))
+        .map(|g| summarize(g))
+        .filter(|g| running.is_none_or(|r| matches!(g.status, GameStatus::Running) == r))
+        .collect::<Vec<_>>();
+
+    games.sort_by_key(|g| Reverse(g.id));
+
+    Ok(Json(games))
+}
+
+#[utoipa::path(
+    tag = "spectate",
+    operation_id = "spectate_game",
+    responses((status = 200, body = GameDetails)),
+)]
+#[get("/games/<id>")]
+pub async fn get_game(state: &AppState, id: u64) -> Result<Json<GameDetails>, Error> {
+    let handle = find_game(state, id)?;
+    let snapshot = handle.snapshot();
+
+    Ok(Json(GameDetails {
+        summary: summarize(&handle),
+        game: snapshot.game,
+        history: snapshot.history,
+        clock: snapshot.clock,
+    }))
+}
+
+/// Read-only equivalent of `/game/events` for any game.
+#[utoipa::path(
+    tag = "spectate",
+    operation_id = "spectate_events",
+    responses((status = 200, description = "Server-Sent Events, each holding a `GameEvent`", content_type = "text/event-stream", body = GameEvent)),
+)]
+#[get("/games/<id>/events")]
+pub async fn events(
+    state: &AppState,
+    id: u64,
+    shutdown: Shutdown,
+) -> Result<EventStream![], Error> {
+    let handle = find_game(state, id)?;
+    Ok(event_stream(&handle, shutdown))
+}
@@ -1,11 +1,13 @@
 use crate::{
+    auth::now,
     config::config,
-    docker::{CPP_IMAGE, JAVA_IMAGE, PYTHON_IMAGE},
+    docker::{self, CPP_IMAGE, JAVA_IMAGE, PYTHON_IMAGE},
 };
 
 use super::{AppState, Error, User};
 use async_process::{Child, Command};
 use base64::{prelude::BASE64_STANDARD, Engine};
+use rand::Rng;
 use rocket::{
     get, post,
     serde::json::Json,
@@ -16,8 +18,27 @@ use rocket::{
 };
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, path::PathBuf, process::Stdio, str::FromStr};
+use tracing::error;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Line printed on stderr by the container of a bot once its code is
+/// compiled, from which the bot's time is counted.
+pub const READY_MESSAGE: &str = "__s4s_ready__";
+
+/// A bot running in its container.
+pub struct RunningBot {
+    pub child: Child,
+    container: String,
+}
+
+impl RunningBot {
+    pub async fn kill(&mut self) {
+        let _ = self.child.kill();
+        docker::kill(&self.container).await;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
     Cpp,
@@ -48,27 +69,42 @@ impl FromStr for Language {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Submission {
+    /// Name of the user or team owning the submission.
     pub name: String,
     pub lang: Language,
     pub code: PathBuf,
+    /// Starts at 1, 0 being the empty submission created at registration.
+    #[serde(default)]
+    pub version: u32,
+    /// Member who uploaded this version.
+    #[serde(default)]
+    pub uploaded_by: Option<String>,
+    /// UNIX timestamp of the upload, in seconds.
+    #[serde(default)]
+    pub uploaded_at: u64,
 }
 
 impl Submission {
     pub fn empty(name: String) -> Result<Self, Error> {
-        let path = PathBuf::from_str(format!("{}/{}", config().data_dir, name).as_str()).unwrap();
+        let lang = Language::Cpp;
+        let path =
+            PathBuf::from_str(format!("{}/{}.{}", config().data_dir, name, lang).as_str()).unwrap();
 
         std::fs::File::create(path.clone())?;
 
         Ok(Self {
             name,
-            lang: Language::Cpp,
+            lang,
             code: path,
+            version: 0,
+            uploaded_by: None,
+            uploaded_at: now(),
         })
     }
 
-    pub async fn start(&self) -> Result<Child, Error> {
+    pub async fn start(&self) -> Result<RunningBot, Error> {
         let metadata = fs::metadata(self.code.clone()).await;
         if metadata.is_err() || metadata.is_ok_and(|m| m.len() == 0) {
             return Err(Error::AIFailed {
@@ -92,22 +128,42 @@ impl Submission {
             Language::Cpp => (
                 CPP_IMAGE,
                 format!(
-                    "echo {base_code} | base64 -d > /script.cpp && g++ /script.cpp -o /exe && /exe"
+                    "echo {base_code} | base64 -d > /script.cpp && g++ /script.cpp -o /exe \
+                    && echo {READY_MESSAGE} >&2 && exec /exe"
                 ),
             ),
+            // Compiled under the name of its public class, as `javac` requires,
+            // or run from source without one.
             Language::Java => (
                 JAVA_IMAGE,
-                format!("echo {base_code} | base64 -d > /script.java && java /script.java"),
+                format!(
+                    "echo {base_code} | base64 -d > /script.java \
+                    && cls=$(sed -n 's/^ *public \\(final \\)\\?class \\([A-Za-z0-9_]*\\).*/\\2/p' \
+                    /script.java | head -n 1) \
+                    && if [ -n \"$cls\" ]; then \
+                    mkdir -p /classes && cp /script.java /classes/$cls.java \
+                    && javac -encoding UTF-8 -d /classes /classes/$cls.java \
+                    && echo {READY_MESSAGE} >&2 && exec java -cp /classes $cls; \
+                    else echo {READY_MESSAGE} >&2 && exec java /script.java; fi"
+                ),
             ),
             Language::Python => (
                 PYTHON_IMAGE,
-                format!("echo {base_code} | base64 -d > /script.py && python /script.py"),
+                format!(
+                    "echo {base_code} | base64 -d > /script.py \
+                    && echo {READY_MESSAGE} >&2 && exec python /script.py"
+                ),
             ),
         };
 
-        Command::new("docker")
+        // Named so that it can be killed when the bot runs out of time.
+        let container = format!("s4s-bot-{:016x}", rand::thread_rng().gen::<u64>());
+        let child = Command::new("docker")
             .args([
                 "run",
+                "--rm",
+                "--name",
+                container.as_str(),
                 "-u",
                 "root",
                 "-i",
@@ -120,24 +176,63 @@ impl Submission {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(Error::from)
+            .map_err(|e| {
+                error!(error = %e, "failed to start the container");
+                Error::InfrastructureFailure
+            })?;
+
+        Ok(RunningBot { child, container })
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct SubmissionStatus {
     code: String,
     lang: Language,
 }
 
-#[get("/submission")]
-pub async fn get_submission(user: User, state: &AppState) -> Result<Json<SubmissionStatus>, Error> {
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SubmissionVersion {
+    version: u32,
+    lang: Language,
+    uploaded_by: Option<String>,
+    uploaded_at: u64,
+}
+
+impl From<&Submission> for SubmissionVersion {
+    fn from(submission: &Submission) -> Self {
+        Self {
+            version: submission.version,
+            lang: submission.lang,
+            uploaded_by: submission.uploaded_by.clone(),
+            uploaded_at: submission.uploaded_at,
+        }
+    }
+}
+
+/// Retrieves the active submission, or the given version of it.
+#[utoipa::path(
+    tag = "submissions",
+    responses((status = 200, body = SubmissionStatus)),
+    security(("session" = [])),
+)]
+#[get("/submission?<version>")]
+pub async fn get_submission(
+    user: User,
+    version: Option<u32>,
+    state: &AppState,
+) -> Result<Json<SubmissionStatus>, Error> {
     let submission = {
         let lock = state.lock()?;
-        lock.submissions
-            .get(&user.name)
-            .ok_or(Error::NotFound)?
-            .clone()
+        match version {
+            Some(version) => lock
+                .submission_history
+                .get(user.owner())
+                .and_then(|h| h.iter().find(|s| s.version == version)),
+            None => lock.submissions.get(user.owner()),
+        }
+        .ok_or(Error::NotFound)?
+        .clone()
     };
 
     let mut code = String::new();
@@ -152,6 +247,13 @@ pub async fn get_submission(user: User, state: &AppState) -> Result<Json<Submiss
     }))
 }
 
+#[utoipa::path(
+    tag = "submissions",
+    params(("lang" = Language, Query)),
+    request_body(content = String, content_type = "text/plain", description = "Source code"),
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
 #[post("/submission?<lang>", data = "<code>")]
 pub async fn post_submission(
     user: User,
@@ -160,27 +262,52 @@ pub async fn post_submission(
     state: &AppState,
 ) -> Result<(), Error> {
     let lang = Language::from_str(lang.as_str())?;
+    // The upload is dated when accepted, so that it cannot miss a contest
+    // closing while the file is written.
+    let uploaded_at = now();
+    let version = {
+        let mut lock = state.lock()?;
+        lock.check_submissions_open(uploaded_at)?;
+        lock.reserve_submission_version(user.owner())
+    };
 
-    let path = PathBuf::from_str(
-        format!("{}/{}.{}", config().data_dir, user.name, lang.to_string()).as_str(),
-    )
-    .unwrap();
-
-    File::create(path.clone())
-        .await
-        .inspect_err(|e| println!("{e:#?}"))?
-        .write_all(code)
-        .await
-        .inspect_err(|e| println!("{e:#?}"))?;
-
-    state.lock().unwrap().submissions.insert(
-        user.name.clone(),
-        Submission {
-            name: user.name,
-            lang,
-            code: path,
-        },
-    );
+    let dir = PathBuf::from(&config().data_dir)
+        .join("submissions")
+        .join(user.owner());
+    fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!("{version}.{lang}"));
+
+    File::create(path.clone()).await?.write_all(code).await?;
+
+    state.lock()?.save_submission(Submission {
+        name: user.owner().to_owned(),
+        lang,
+        code: path,
+        version,
+        uploaded_by: Some(user.name),
+        uploaded_at,
+    })
+}
+
+/// Lists every uploaded version of the submission, oldest first.
+#[utoipa::path(
+    tag = "submissions",
+    responses((status = 200, body = Vec<SubmissionVersion>)),
+    security(("session" = [])),
+)]
+#[get("/submission/history")]
+pub async fn get_submission_history(
+    user: User,
+    state: &AppState,
+) -> Result<Json<Vec<SubmissionVersion>>, Error> {
+    let lock = state.lock()?;
 
-    Ok(())
+    Ok(Json(
+        lock.submission_history
+            .get(user.owner())
+            .into_iter()
+            .flatten()
+            .map(SubmissionVersion::from)
+            .collect(),
+    ))
 }
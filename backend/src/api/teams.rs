@@ -0,0 +1,239 @@
+use super::{is_valid_name, AppState, Error, State, User};
+use crate::{config::config, game::GameStatus, storage::Collection};
+use rand::{distributions::Uniform, Rng};
+use rocket::{get, post, serde::json::Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+const JOIN_CODE_LENGTH: usize = 8;
+/// Letters and digits that cannot be mistaken for one another.
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// A group of users sharing the same submissions and games.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Team {
+    pub name: String,
+    pub members: Vec<String>,
+    /// Code to give to other users so that they can join the team.
+    pub join_code: String,
+}
+
+fn generate_join_code() -> String {
+    let distribution = Uniform::from(0..JOIN_CODE_ALPHABET.len());
+
+    rand::thread_rng()
+        .sample_iter(distribution)
+        .take(JOIN_CODE_LENGTH)
+        .map(|i| JOIN_CODE_ALPHABET[i] as char)
+        .collect()
+}
+
+impl State {
+    fn save_team(&mut self, team: Team) -> Result<(), Error> {
+        self.storage.save(Collection::Teams, &team.name, &team)?;
+        self.teams.insert(team.name.clone(), team);
+
+        Ok(())
+    }
+
+    /// Fails if the user plays a running game under their own name, which
+    /// they could no longer play once their games are those of a team.
+    fn check_no_running_games(&self, user: &str) -> Result<(), Error> {
+        let running = self
+            .games
+            .values()
+            .any(|g| g.info.involves(user) && matches!(g.state().status, GameStatus::Running));
+        if running {
+            return Err(Error::GamesInProgress);
+        }
+
+        Ok(())
+    }
+
+    fn set_team(&mut self, user: &str, team: Option<String>) -> Result<(), Error> {
+        let mut record = self.users.get(user).ok_or(Error::NotFound)?.clone();
+        record.team = team;
+
+        self.storage.save(Collection::Users, user, &record)?;
+        self.users.insert(user.to_owned(), record);
+
+        Ok(())
+    }
+
+    pub fn create_team(&mut self, user: &str, name: &str) -> Result<Team, Error> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidName);
+        }
+        // Teams and users share the namespace of submissions and games, even
+        // the creator's own name.
+        if self.teams.contains_key(name) || self.users.contains_key(name) {
+            return Err(Error::Conflict);
+        }
+        if self.users.get(user).is_some_and(|u| u.team.is_some()) {
+            return Err(Error::Conflict);
+        }
+        self.check_no_running_games(user)?;
+
+        let team = Team {
+            name: name.to_owned(),
+            members: vec![user.to_owned()],
+            join_code: generate_join_code(),
+        };
+
+        self.save_team(team.clone())?;
+        self.set_team(user, Some(team.name.clone()))?;
+
+        Ok(team)
+    }
+
+    pub fn join_team(&mut self, user: &str, join_code: &str) -> Result<Team, Error> {
+        let mut team = self
+            .teams
+            .values()
+            .find(|t| t.join_code == join_code)
+            .ok_or(Error::NotFound)?
+            .clone();
+
+        if self.users.get(user).is_some_and(|u| u.team.is_some()) {
+            return Err(Error::Conflict);
+        }
+        self.check_no_running_games(user)?;
+        if team.members.len() >= config().team_size {
+            return Err(Error::TeamFull);
+        }
+
+        team.members.push(user.to_owned());
+
+        self.save_team(team.clone())?;
+        self.set_team(user, Some(team.name.clone()))?;
+
+        Ok(team)
+    }
+
+    pub fn leave_team(&mut self, user: &str) -> Result<(), Error> {
+        let name = self
+            .users
+            .get(user)
+            .and_then(|u| u.team.clone())
+            .ok_or(Error::NotFound)?;
+
+        if let Some(mut team) = self.teams.get(&name).cloned() {
+            team.members.retain(|m| m != user);
+            self.save_team(team)?;
+        }
+
+        self.set_team(user, None)
+    }
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses((status = 200, body = Team)),
+    security(("session" = [])),
+)]
+#[get("/team")]
+pub async fn get_team(state: &AppState, user: User) -> Result<Json<Team>, Error> {
+    let team = user.team.ok_or(Error::NotFound)?;
+    let lock = state.lock()?;
+
+    Ok(Json(lock.teams.get(&team).ok_or(Error::NotFound)?.clone()))
+}
+
+/// Creates a team with the user as its only member.
+#[utoipa::path(
+    tag = "teams",
+    responses((status = 200, body = Team)),
+    security(("session" = [])),
+)]
+#[post("/team?<name>")]
+pub async fn create_team(state: &AppState, user: User, name: &str) -> Result<Json<Team>, Error> {
+    Ok(Json(state.lock()?.create_team(&user.name, name)?))
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses((status = 200, body = Team)),
+    security(("session" = [])),
+)]
+#[post("/team/join?<code>")]
+pub async fn join_team(state: &AppState, user: User, code: &str) -> Result<Json<Team>, Error> {
+    Ok(Json(state.lock()?.join_team(&user.name, code)?))
+}
+
+#[utoipa::path(
+    tag = "teams",
+    responses((status = 200, description = "Done")),
+    security(("session" = [])),
+)]
+#[post("/team/leave")]
+pub async fn leave_team(state: &AppState, user: User) -> Result<(), Error> {
+    state.lock()?.leave_team(&user.name)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::{
+            play::{Game, GameHandle, GameInfo, Side},
+            Error, State,
+        },
+        game::GameState,
+        storage::sqlite::SqliteStorage,
+    };
+    use std::{path::Path, sync::Arc};
+
+    fn state() -> State {
+        let storage = SqliteStorage::open(Path::new(":memory:")).unwrap();
+        let mut state = State::load_from(Arc::new(storage)).unwrap();
+        for name in ["alice", "bob"] {
+            state.register(name, None).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn team_names_are_not_user_names() {
+        let mut state = state();
+
+        assert!(matches!(
+            state.create_team("alice", "alice"),
+            Err(Error::Conflict)
+        ));
+        assert!(matches!(
+            state.create_team("alice", "bob"),
+            Err(Error::Conflict)
+        ));
+        assert!(state.create_team("alice", "team").is_ok());
+    }
+
+    #[test]
+    fn refuses_to_join_while_playing() {
+        let mut state = state();
+        let team = state.create_team("alice", "team").unwrap();
+
+        let id = state.new_game_id();
+        let info = GameInfo {
+            id,
+            owner: "bob".to_owned(),
+            white: Side::Human("bob".to_owned()),
+            black: Side::Engine,
+            contest: None,
+            hints: true,
+        };
+        let game = Arc::new(GameHandle::new(info, Game::new(GameState::default())));
+        state.games.insert(id, game.clone());
+
+        assert!(matches!(
+            state.join_team("bob", &team.join_code),
+            Err(Error::GamesInProgress)
+        ));
+        assert!(matches!(
+            state.create_team("bob", "other"),
+            Err(Error::GamesInProgress)
+        ));
+
+        state.games.remove(&id);
+        state.retire_game(game);
+        assert!(state.create_team("bob", "other").is_ok());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::config::config;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    path::Path,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const PASSWORD_ROUNDS: u32 = 100_000;
+
+/// Content of a session token.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Claims {
+    /// Name of the user the token has been issued to.
+    pub sub: String,
+    /// Expiration date, as a UNIX timestamp in seconds.
+    pub exp: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// The key used to sign tokens. Taken from `SECRET_KEY` if set, otherwise
+/// generated once and stored in `DATA_DIR` so that sessions survive restarts.
+fn secret() -> &'static [u8] {
+    static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+    SECRET.get_or_init(|| {
+        if let Some(key) = config().secret_key.as_ref().filter(|k| !k.is_empty()) {
+            return key.as_bytes().to_vec();
+        }
+
+        let path = Path::new(&config().data_dir).join("secret.key");
+        match std::fs::read(&path) {
+            Ok(key) if !key.is_empty() => key,
+            _ => {
+                let key = random_bytes::<32>().to_vec();
+                if let Err(err) = std::fs::write(&path, &key) {
+                    error!(?path, error = %err, "failed to save the secret key");
+                }
+                key
+            }
+        }
+    })
+}
+
+fn sign(key: &[u8], payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn encode_token(key: &[u8], claims: &Claims) -> String {
+    let payload = BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+    let signature = sign(key, &payload).finalize().into_bytes();
+
+    format!("{payload}.{}", BASE64_URL_SAFE_NO_PAD.encode(signature))
+}
+
+fn decode_token(key: &[u8], token: &str, now: u64) -> Option<Claims> {
+    let (payload, signature) = token.split_once('.')?;
+
+    sign(key, payload)
+        .verify_slice(&BASE64_URL_SAFE_NO_PAD.decode(signature).ok()?)
+        .ok()?;
+
+    let claims: Claims =
+        serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+
+    (claims.exp > now).then_some(claims)
+}
+
+/// Issues a session token for the given user, valid for `SESSION_DURATION`.
+pub fn issue_token(name: &str) -> (String, Claims) {
+    let claims = Claims {
+        sub: name.to_owned(),
+        exp: now() + config().session_duration,
+    };
+
+    (encode_token(secret(), &claims), claims)
+}
+
+/// Returns the claims of a token if it has been issued by this server and has
+/// not expired yet.
+pub fn verify_token(token: &str) -> Option<Claims> {
+    decode_token(secret(), token, now())
+}
+
+/// Hashes a password with PBKDF2, as `<rounds>$<salt>$<hash>`.
+pub fn hash_password(password: &str) -> String {
+    let salt = random_bytes::<16>();
+    let hash = pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), &salt, PASSWORD_ROUNDS);
+
+    format!(
+        "{PASSWORD_ROUNDS}${}${}",
+        BASE64_URL_SAFE_NO_PAD.encode(salt),
+        BASE64_URL_SAFE_NO_PAD.encode(hash)
+    )
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    let verify = || {
+        let mut parts = hash.splitn(3, '$');
+        let rounds = parts.next()?.parse().ok()?;
+        let salt = BASE64_URL_SAFE_NO_PAD.decode(parts.next()?).ok()?;
+        let expected = BASE64_URL_SAFE_NO_PAD.decode(parts.next()?).ok()?;
+
+        let mut actual = vec![0; expected.len()];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, rounds, &mut actual);
+
+        // Constant-time comparison, to avoid leaking timing information.
+        Some(
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(&expected)
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0,
+        )
+    };
+
+    verify().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_token, encode_token, hash_password, verify_password, Claims};
+
+    #[test]
+    fn token_roundtrip() {
+        let claims = Claims {
+            sub: "alice".to_owned(),
+            exp: 100,
+        };
+        let token = encode_token(b"key", &claims);
+
+        assert_eq!(decode_token(b"key", &token, 99), Some(claims));
+        assert_eq!(decode_token(b"key", &token, 100), None);
+        assert_eq!(decode_token(b"other key", &token, 99), None);
+        assert_eq!(decode_token(b"key", "alice", 99), None);
+    }
+
+    #[test]
+    fn token_tampering() {
+        let token = encode_token(
+            b"key",
+            &Claims {
+                sub: "alice".to_owned(),
+                exp: 100,
+            },
+        );
+        let forged = encode_token(
+            b"key",
+            &Claims {
+                sub: "bob".to_owned(),
+                exp: 100,
+            },
+        );
+
+        let (_, signature) = token.split_once('.').unwrap();
+        let (payload, _) = forged.split_once('.').unwrap();
+
+        assert_eq!(
+            decode_token(b"key", &format!("{payload}.{signature}"), 99),
+            None
+        );
+    }
+
+    #[test]
+    fn password() {
+        let hash = hash_password("hunter2");
+
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert!(!verify_password("hunter2", "garbage"));
+        assert_ne!(hash, hash_password("hunter2"));
+    }
+}
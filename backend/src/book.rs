@@ -0,0 +1,401 @@
+use crate::{
+    api::Error,
+    config::config,
+    game::{GameState, Move, Position, Variant},
+};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+/// Number of sequences of moves of each game kept in the book.
+const MAX_PLIES: usize = 24;
+
+/// A sequence of moves of an opening, weighted by how often it was played.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct BookMove {
+    pub moves: Vec<Move>,
+    pub weight: u32,
+}
+
+/// Known sequences of moves by position key, stored as JSON.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct Book {
+    positions: HashMap<u64, Vec<BookMove>>,
+}
+
+impl Book {
+    /// Reads a book saved as JSON, or builds it from a PDN collection of games
+    /// if the file ends with `.pdn`.
+    pub fn load(path: &Path) -> Result<Book, Error> {
+        let text = std::fs::read_to_string(path)?;
+
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("pdn"))
+        {
+            let mut book = Book::default();
+            book.add_pdn(&text);
+            Ok(book)
+        } else {
+            Ok(serde_json::from_str(&text)?)
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The sequences of moves known for the position, most played first.
+    pub fn moves(&self, state: &GameState) -> &[BookMove] {
+        self.positions
+            .get(&state.position_key())
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn add(&mut self, state: &GameState, moves: &[Move], weight: u32) {
+        let entries = self.positions.entry(state.position_key()).or_default();
+
+        match entries.iter_mut().find(|e| e.moves == moves) {
+            Some(entry) => entry.weight += weight,
+            None => entries.push(BookMove {
+                moves: moves.to_vec(),
+                weight,
+            }),
+        }
+        entries.sort_by_key(|e| std::cmp::Reverse(e.weight));
+    }
+
+    /// A legal sequence of moves of the book, picked at random according to
+    /// the weights, or `None` if the position is not in the book.
+    pub fn choose(&self, state: &GameState, rng: &mut impl Rng) -> Option<Vec<Move>> {
+        let legal = state.list_valid_moves();
+        let entries = self
+            .moves(state)
+            .iter()
+            .filter(|e| legal.iter().any(|(moves, _)| *moves == e.moves))
+            .collect::<Vec<_>>();
+
+        let index = WeightedIndex::new(entries.iter().map(|e| e.weight)).ok()?;
+        Some(entries[index.sample(rng)].moves.clone())
+    }
+
+    /// Adds the first moves of every game of a PDN collection. Games of other
+    /// variants than international, frisian and canadian, and those starting
+    /// from a `FEN` setup, are skipped. Returns the number of games added.
+    pub fn add_pdn(&mut self, text: &str) -> usize {
+        let mut added = 0;
+        for game in parse_pdn(text) {
+            if self.add_game(&game) {
+                added += 1;
+            }
+        }
+        added
+    }
+
+    fn add_game(&mut self, game: &PdnGame) -> bool {
+        let Some(variant) = game.variant else {
+            return false;
+        };
+        if game.setup || game.moves.is_empty() {
+            return false;
+        }
+
+        let mut state = GameState::new(variant);
+        for notation in game.moves.iter().take(MAX_PLIES) {
+            let Some(moves) = resolve_move(&state, notation) else {
+                break;
+            };
+            self.add(&state, &moves, 1);
+            if state.apply_sequence(&moves).is_err() {
+                break;
+            }
+        }
+
+        true
+    }
+}
+
+/// The book given by `OPENING_BOOK`, empty if there is none or it could not be
+/// read.
+pub fn book() -> &'static Book {
+    static BOOK: OnceLock<Book> = OnceLock::new();
+
+    BOOK.get_or_init(|| {
+        let Some(path) = config().opening_book.as_ref().filter(|p| !p.is_empty()) else {
+            return Book::default();
+        };
+
+        match Book::load(Path::new(path)) {
+            Ok(book) => {
+                info!(path, positions = book.len(), "loaded the opening book");
+                book
+            }
+            Err(err) => {
+                error!(path, error = ?err, "failed to load the opening book");
+                Book::default()
+            }
+        }
+    })
+}
+
+#[derive(Debug)]
+struct PdnGame {
+    /// `None` if the `GameType` is not supported.
+    variant: Option<Variant>,
+    /// Whether the game starts from a `FEN` position.
+    setup: bool,
+    moves: Vec<String>,
+}
+
+impl Default for PdnGame {
+    fn default() -> Self {
+        Self {
+            variant: Some(Variant::International),
+            setup: false,
+            moves: vec![],
+        }
+    }
+}
+
+/// The variant of a PDN `GameType`, if its squares are numbered like in
+/// international draughts.
+fn pdn_variant(game_type: &str) -> Option<Variant> {
+    let number = game_type.split(',').next()?.trim();
+
+    match number {
+        "20" => Some(Variant::International),
+        "27" => Some(Variant::Canadian),
+        "40" => Some(Variant::Frisian),
+        _ => None,
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "*" | "2-0" | "0-2" | "1-1" | "0-0" | "1-0" | "0-1")
+}
+
+/// Splits a PDN collection into games, dropping comments and variations.
+fn parse_pdn(text: &str) -> Vec<PdnGame> {
+    let mut games = vec![];
+    let mut game = PdnGame::default();
+    let mut chars = text.chars().peekable();
+
+    let finish = |games: &mut Vec<PdnGame>, game: &mut PdnGame| {
+        let game = std::mem::take(game);
+        if !game.moves.is_empty() {
+            games.push(game);
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                if !game.moves.is_empty() {
+                    finish(&mut games, &mut game);
+                }
+
+                let tag = chars.by_ref().take_while(|&c| c != ']').collect::<String>();
+                let (name, value) = tag.split_once(char::is_whitespace).unwrap_or((&tag, ""));
+                let value = value.trim().trim_matches('"');
+                match name {
+                    "GameType" => game.variant = pdn_variant(value),
+                    "FEN" => game.setup = true,
+                    _ => (),
+                }
+            }
+            '{' => chars.by_ref().take_while(|&c| c != '}').for_each(drop),
+            '(' => {
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' if depth == 1 => break,
+                        ')' => depth -= 1,
+                        _ => (),
+                    }
+                }
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[{(".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+
+                if is_result(&token) {
+                    finish(&mut games, &mut game);
+                    continue;
+                }
+
+                // Move numbers may be attached to the move, as in `1.32-28`.
+                let notation = token
+                    .rsplit('.')
+                    .next()
+                    .unwrap_or_default()
+                    .trim_end_matches(['!', '?', '*', '+']);
+                if notation.starts_with(|c: char| c.is_ascii_digit())
+                    && notation.contains(['-', 'x'])
+                {
+                    game.moves.push(notation.to_owned());
+                }
+            }
+        }
+    }
+
+    finish(&mut games, &mut game);
+    games
+}
+
+/// The dark square with the given PDN number, counted from 1 on the top left
+/// and row by row.
+fn square(number: usize, size: usize) -> Option<Position> {
+    let per_row = size / 2;
+    if number == 0 || number > per_row * size {
+        return None;
+    }
+
+    let row = (number - 1) / per_row;
+    let column = 2 * ((number - 1) % per_row) + usize::from(row.is_multiple_of(2));
+    Some((row, column))
+}
+
+/// The legal sequence of moves written as `<from>-<to>`, or as
+/// `<from>x<to>` for captures, optionally with the squares in between.
+fn resolve_move(state: &GameState, notation: &str) -> Option<Vec<Move>> {
+    let size = state.variant.rules().size;
+    let squares = notation
+        .split(['-', 'x'])
+        .map(|n| square(n.parse().ok()?, size))
+        .collect::<Option<Vec<_>>>()?;
+    let (first, last) = (*squares.first()?, *squares.last()?);
+
+    state
+        .list_valid_moves()
+        .into_iter()
+        .map(|(moves, _)| moves)
+        .find(|moves| {
+            let landings = moves.iter().map(|m| m.to).collect::<Vec<_>>();
+
+            moves.first().is_some_and(|m| m.from == first)
+                && landings.last() == Some(&last)
+                && (squares.len() == 2 || landings == squares[1..])
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{square, Book, BookMove};
+    use crate::game::{GameState, Move, Variant};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const GAMES: &str = r#"
+[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-1"]
+1. 32-28 19-23 2. 28x19 14x23 {A comment (with parentheses)} 3. 37-32 (3. 33-28 23x32 37x28) 10-14 1-1
+
+[Event "Test"]
+[Result "2-0"]
+1.32-28 19-23 2.28x19 14x23 3.33-28! 2-0
+
+[GameType "21"]
+1. 11-15 23-19 *
+
+[FEN "W:W31:B20"]
+1. 31-27 *
+"#;
+
+    fn m(from: (usize, usize), to: (usize, usize)) -> Move {
+        Move { from, to }
+    }
+
+    #[test]
+    fn numbers_squares() {
+        assert_eq!(square(1, 10), Some((0, 1)));
+        assert_eq!(square(6, 10), Some((1, 0)));
+        assert_eq!(square(50, 10), Some((9, 8)));
+        assert_eq!(square(72, 12), Some((11, 10)));
+        assert_eq!(square(0, 10), None);
+        assert_eq!(square(51, 10), None);
+    }
+
+    #[test]
+    fn reads_pdn_games() {
+        let mut book = Book::default();
+        assert_eq!(book.add_pdn(GAMES), 2);
+
+        let mut state = GameState::new(Variant::International);
+        assert_eq!(
+            book.moves(&state),
+            [BookMove {
+                moves: vec![m((6, 3), (5, 4))],
+                weight: 2,
+            }]
+        );
+
+        for moves in [
+            vec![m((6, 3), (5, 4))],
+            vec![m((3, 6), (4, 5))],
+            vec![m((5, 4), (3, 6))],
+            vec![m((2, 7), (4, 5))],
+        ] {
+            state.apply_sequence(&moves).unwrap();
+        }
+
+        assert_eq!(
+            book.moves(&state)
+                .iter()
+                .map(|e| (e.moves.clone(), e.weight))
+                .collect::<Vec<_>>(),
+            [(vec![m((7, 2), (6, 3))], 1), (vec![m((6, 5), (5, 4))], 1),]
+        );
+        assert!(book.moves(&GameState::new(Variant::English)).is_empty());
+    }
+
+    #[test]
+    fn chooses_legal_moves() {
+        let mut state = GameState::new(Variant::International);
+        let mut book = Book::default();
+        book.add(&state, &[m((6, 3), (5, 4))], 3);
+        book.add(&state, &[m((6, 3), (4, 5))], 100);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            assert_eq!(book.choose(&state, &mut rng), Some(vec![m((6, 3), (5, 4))]));
+        }
+
+        state.apply_sequence(&[m((6, 3), (5, 4))]).unwrap();
+        assert_eq!(book.choose(&state, &mut rng), None);
+    }
+
+    #[test]
+    fn saves_as_json() {
+        let state = GameState::new(Variant::International);
+        let mut book = Book::default();
+        book.add(&state, &[m((6, 3), (5, 4))], 3);
+
+        let path = std::env::temp_dir().join(format!("book-{}.json", std::process::id()));
+        book.save(&path).unwrap();
+        let loaded = Book::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.moves(&state), book.moves(&state));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::game::Player;
+use rocket::FromFormField;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use utoipa::ToSchema;
+
+/// Milliseconds since the UNIX epoch.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[derive(
+    Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum ClockKind {
+    /// The increment is added after each move.
+    #[default]
+    Fischer,
+    /// The time used for a move is given back, up to the increment.
+    Bronstein,
+}
+
+/// How much time each player has.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub struct TimeControl {
+    pub kind: ClockKind,
+    pub initial_ms: u64,
+    pub increment_ms: u64,
+}
+
+/// The remaining time of both players. Only the current player's time runs,
+/// from `turn_started_at`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Clock {
+    #[serde(flatten)]
+    pub control: TimeControl,
+    /// Remaining time of white at the start of the current turn.
+    pub white_ms: u64,
+    /// Remaining time of black at the start of the current turn.
+    pub black_ms: u64,
+    /// When the current turn started, in milliseconds since the UNIX epoch, or
+    /// `None` once the clock is stopped.
+    pub turn_started_at: Option<u64>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl, now: u64) -> Self {
+        Self {
+            control,
+            white_ms: control.initial_ms,
+            black_ms: control.initial_ms,
+            turn_started_at: Some(now),
+        }
+    }
+
+    fn time_mut(&mut self, player: Player) -> &mut u64 {
+        match player {
+            Player::White => &mut self.white_ms,
+            Player::Black => &mut self.black_ms,
+        }
+    }
+
+    fn elapsed(&self, now: u64) -> u64 {
+        self.turn_started_at
+            .map_or(0, |start| now.saturating_sub(start))
+    }
+
+    /// Remaining time of the player, whose time runs if it is the `current`
+    /// player.
+    pub fn remaining(&self, player: Player, current: Player, now: u64) -> u64 {
+        let time = match player {
+            Player::White => self.white_ms,
+            Player::Black => self.black_ms,
+        };
+
+        if player == current {
+            time.saturating_sub(self.elapsed(now))
+        } else {
+            time
+        }
+    }
+
+    /// Charges the time elapsed since the start of the turn to the player, and
+    /// starts the next turn. Returns the time used.
+    fn charge(&mut self, player: Player, now: u64) -> u64 {
+        let elapsed = self.elapsed(now);
+        let time = self.time_mut(player);
+        *time = time.saturating_sub(elapsed);
+        self.turn_started_at = Some(now);
+
+        elapsed
+    }
+
+    /// Ends the turn of the player after a move, adding the increment.
+    pub fn press(&mut self, player: Player, now: u64) {
+        let used = self.charge(player, now);
+        let increment = match self.control.kind {
+            ClockKind::Fischer => self.control.increment_ms,
+            ClockKind::Bronstein => self.control.increment_ms.min(used),
+        };
+        *self.time_mut(player) += increment;
+    }
+
+    /// Ends the turn of the player without a move nor increment, e.g. when a
+    /// takeback changes the current player.
+    pub fn switch(&mut self, player: Player, now: u64) {
+        self.charge(player, now);
+    }
+
+    /// Restarts the current turn without charging the time elapsed so far.
+    pub fn restart(&mut self, now: u64) {
+        if self.turn_started_at.is_some() {
+            self.turn_started_at = Some(now);
+        }
+    }
+
+    pub fn stop(&mut self, player: Player, now: u64) {
+        if self.turn_started_at.is_some() {
+            self.charge(player, now);
+            self.turn_started_at = None;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.turn_started_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Clock, ClockKind, TimeControl};
+    use crate::game::Player;
+
+    fn clock(kind: ClockKind) -> Clock {
+        Clock::new(
+            TimeControl {
+                kind,
+                initial_ms: 10_000,
+                increment_ms: 2_000,
+            },
+            0,
+        )
+    }
+
+    #[test]
+    fn fischer_adds_the_increment() {
+        let mut clock = clock(ClockKind::Fischer);
+
+        clock.press(Player::White, 500);
+        assert_eq!(clock.white_ms, 11_500);
+        assert_eq!(clock.remaining(Player::Black, Player::Black, 4_500), 6_000);
+
+        clock.press(Player::Black, 9_000);
+        assert_eq!(clock.black_ms, 3_500);
+    }
+
+    #[test]
+    fn bronstein_gives_back_the_time_used() {
+        let mut clock = clock(ClockKind::Bronstein);
+
+        clock.press(Player::White, 500);
+        assert_eq!(clock.white_ms, 10_000);
+
+        clock.press(Player::Black, 5_500);
+        assert_eq!(clock.black_ms, 7_000);
+    }
+
+    #[test]
+    fn stopped_clock_does_not_run() {
+        let mut clock = clock(ClockKind::Fischer);
+
+        clock.stop(Player::White, 1_000);
+        assert_eq!(clock.remaining(Player::White, Player::White, 5_000), 9_000);
+        assert!(!clock.is_running());
+    }
+}
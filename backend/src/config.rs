@@ -1,3 +1,4 @@
+use crate::{logging::LogFormat, storage::StorageKind};
 use envconfig::Envconfig;
 use std::sync::OnceLock;
 
@@ -5,9 +6,112 @@ use std::sync::OnceLock;
 pub struct Config {
     #[envconfig(from = "DATA_DIR")]
     pub data_dir: String,
+    /// Either `sqlite` or `json`.
+    #[envconfig(from = "STORAGE", default = "sqlite")]
+    pub storage: StorageKind,
+    /// Key used to sign session tokens. Generated and stored in `DATA_DIR`
+    /// when not set.
+    #[envconfig(from = "SECRET_KEY")]
+    pub secret_key: Option<String>,
+    /// Validity of a session token, in seconds.
+    #[envconfig(from = "SESSION_DURATION", default = "604800")]
+    pub session_duration: u64,
+    /// Accepts `Authorization: Bearer <name>` without a token. Only meant for
+    /// local testing.
+    #[envconfig(from = "AUTH_DEV_MODE", default = "false")]
+    pub auth_dev_mode: bool,
+    /// Maximum number of members of a team.
+    #[envconfig(from = "TEAM_SIZE", default = "4")]
+    pub team_size: usize,
+    /// Comma-separated names of the organizers.
+    #[envconfig(from = "ADMINS", default = "")]
+    pub admins: String,
+    /// Password required to register under one of the names of `ADMINS`.
+    /// Without it, those names can only be registered by an existing admin.
+    #[envconfig(from = "ADMIN_PASSWORD")]
+    pub admin_password: Option<String>,
+    /// Number of jobs executed concurrently, and of games played at once by
+    /// each contest.
+    #[envconfig(from = "WORKERS", default = "4")]
+    pub workers: usize,
+    /// Number of times a bot is restarted after an infrastructure failure
+    /// (Docker or I/O error) before its job fails.
+    #[envconfig(from = "JOB_RETRIES", default = "2")]
+    pub job_retries: u32,
+    /// Whether bots and the built-in engine accept the takebacks requested by
+    /// their opponent, or decline them.
+    #[envconfig(from = "AI_TAKEBACKS", default = "true")]
+    pub ai_takebacks: bool,
+    /// Filter of the logs, as `<level>` or `<target>=<level>` directives
+    /// separated by commas.
+    #[envconfig(from = "LOG_LEVEL", default = "info")]
+    pub log_level: String,
+    /// Either `text` or `json`.
+    #[envconfig(from = "LOG_FORMAT", default = "text")]
+    pub log_format: LogFormat,
+    /// Path of the opening book, either a PDN collection of games or a book
+    /// saved as JSON.
+    #[envconfig(from = "OPENING_BOOK")]
+    pub opening_book: Option<String>,
+    /// Largest number of pieces of the positions of the endgame tablebase,
+    /// which is disabled below 2.
+    #[envconfig(from = "TABLEBASE_PIECES", default = "4")]
+    pub tablebase_pieces: usize,
+}
+
+impl Config {
+    pub fn is_admin(&self, name: &str) -> bool {
+        self.admins
+            .split(',')
+            .map(str::trim)
+            .any(|admin| !admin.is_empty() && admin == name)
+    }
+
+    /// Whether an account may be registered under the name on its first
+    /// login, which for the names of `ADMINS` takes `ADMIN_PASSWORD`, so that
+    /// nobody takes them before their organizer.
+    pub fn may_register(&self, name: &str, password: &str) -> bool {
+        !self.is_admin(name)
+            || self
+                .admin_password
+                .as_deref()
+                .is_some_and(|p| !p.is_empty() && p == password)
+    }
 }
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
 pub fn config() -> &'static Config {
     CONFIG.get_or_init(|| Config::init_from_env().unwrap())
 }
+
+#[cfg(test)]
+mod test {
+    use super::Config;
+    use envconfig::Envconfig;
+    use std::collections::HashMap;
+
+    fn with_admin_password(admin_password: Option<&str>) -> Config {
+        let mut env = HashMap::from([
+            ("DATA_DIR".to_owned(), "/data".to_owned()),
+            ("ADMINS".to_owned(), "root, ,alice".to_owned()),
+        ]);
+        if let Some(password) = admin_password {
+            env.insert("ADMIN_PASSWORD".to_owned(), password.to_owned());
+        }
+        Config::init_from_hashmap(&env).unwrap()
+    }
+
+    #[test]
+    fn admin_names_need_the_admin_password() {
+        let config = with_admin_password(None);
+        assert!(config.is_admin("alice"));
+        assert!(!config.is_admin(""));
+        assert!(!config.may_register("root", "anything"));
+        assert!(!config.may_register("root", ""));
+        assert!(config.may_register("bob", "anything"));
+
+        let config = with_admin_password(Some("secret"));
+        assert!(!config.may_register("root", "guess"));
+        assert!(config.may_register("root", "secret"));
+    }
+}
@@ -1,4 +1,5 @@
 use std::process::Command;
+use tracing::{error, warn};
 
 pub const JAVA_IMAGE: &str = "cimg/openjdk:17.0";
 pub const PYTHON_IMAGE: &str = "python:3-bullseye";
@@ -9,7 +10,26 @@ pub const IMAGES: [&str; 3] = [JAVA_IMAGE, PYTHON_IMAGE, CPP_IMAGE];
 pub fn pull_required_images() {
     for image in IMAGES {
         if let Err(err) = Command::new("docker").args(["pull", image]).status() {
-            println!("Error while pulling {image}: {err:#?}");
+            error!(image, error = %err, "failed to pull image");
         }
     }
 }
+
+/// Stops a running container. Killing the `docker run` client only stops the
+/// client, while the container keeps running.
+pub async fn kill(container: &str) {
+    let status = async_process::Command::new("docker")
+        .args(["kill", container])
+        .output()
+        .await;
+    match status {
+        Ok(output) if output.status.success() => (),
+        // The container may have exited meanwhile.
+        Ok(output) => warn!(
+            container,
+            error = %String::from_utf8_lossy(&output.stderr).trim(),
+            "failed to kill the container"
+        ),
+        Err(err) => error!(container, error = %err, "failed to kill the container"),
+    }
+}
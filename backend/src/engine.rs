@@ -0,0 +1,250 @@
+use crate::{
+    book::book,
+    game::{GameState, GameStatus, Move, MoveSequence, PieceType, Player},
+    tablebase::{Probe, Wdl},
+};
+
+/// Number of turns the engine looks ahead when playing.
+pub const DEPTH: u32 = 4;
+
+pub const MAN_VALUE: i32 = 100;
+const KING_VALUE: i32 = 300;
+/// Base score of a win. A win found by the search scores `WIN_VALUE` plus the
+/// remaining depth, and one found in the tablebase `WIN_VALUE` minus its
+/// distance in plies, which stays under 256: every win scores above 99000,
+/// far from any material balance.
+const WIN_VALUE: i32 = 100_000;
+
+/// Difference of material between the given player and their opponent.
+fn evaluate(state: &GameState, player: Player) -> i32 {
+    state
+        .board
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|piece| {
+            let value = match piece.type_ {
+                PieceType::Man => MAN_VALUE,
+                PieceType::King => KING_VALUE,
+            };
+
+            if piece.player == player {
+                value
+            } else {
+                -value
+            }
+        })
+        .sum()
+}
+
+/// Exact score of a position of the tablebase for the player to move, on the
+/// scale of the wins found by the search.
+fn tablebase_score(probe: Probe, depth: u32) -> i32 {
+    let quickness = depth as i32 - probe.distance.unwrap_or_default() as i32;
+    match probe.wdl {
+        Wdl::Win => WIN_VALUE + quickness,
+        Wdl::Draw => 0,
+        Wdl::Loss => -WIN_VALUE - quickness,
+    }
+}
+
+/// Score of the position for the player to move, searched with alpha-beta
+/// pruning. Quicker wins and slower losses score better.
+fn negamax(state: &GameState, depth: u32, mut alpha: i32, beta: i32) -> i32 {
+    let player = state.current_player;
+
+    match state.status.winner() {
+        Some(winner) if winner == player => return WIN_VALUE + depth as i32,
+        Some(_) => return -WIN_VALUE - depth as i32,
+        None if matches!(state.status, GameStatus::Draw) => return 0,
+        None => (),
+    }
+
+    if let Some(probe) = state.probe() {
+        return tablebase_score(probe, depth);
+    }
+
+    if depth == 0 {
+        return evaluate(state, player);
+    }
+
+    let sequences = state.list_valid_moves();
+    if sequences.is_empty() {
+        // A player who cannot move loses.
+        return -WIN_VALUE - depth as i32;
+    }
+
+    let mut best = i32::MIN + 1;
+    for (moves, _) in sequences {
+        let mut next = state.clone();
+        if next.apply_sequence(&moves).is_err() {
+            continue;
+        }
+
+        best = best.max(-negamax(&next, depth - 1, -beta, -alpha));
+        alpha = alpha.max(best);
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    best
+}
+
+/// The sequence of moves the engine plays for the current player along with
+/// its score, positive when the engine expects to win, or `None` if the
+/// player cannot move.
+pub fn search(state: &GameState, depth: u32) -> Option<(Vec<Move>, i32)> {
+    let mut best = None;
+    let mut alpha = i32::MIN + 1;
+
+    for (moves, _) in state.list_valid_moves() {
+        let mut next = state.clone();
+        if next.apply_sequence(&moves).is_err() {
+            continue;
+        }
+
+        let score = -negamax(&next, depth.saturating_sub(1), i32::MIN + 1, -alpha);
+        if best.is_none() || score > alpha {
+            alpha = score;
+            best = Some((moves, score));
+        }
+    }
+
+    best
+}
+
+/// The sequence of moves the engine plays: one of the opening book if the
+/// position is in it, otherwise the result of the search.
+pub fn play(state: &GameState) -> Option<(Vec<Move>, i32)> {
+    match book().choose(state, &mut rand::thread_rng()) {
+        // Openings of the book are considered balanced.
+        Some(moves) => Some((moves, 0)),
+        None => search(state, DEPTH),
+    }
+}
+
+/// Every legal sequence of moves of the current player with its exact score
+/// for that player, best first.
+pub fn evaluate_moves(state: &GameState, depth: u32) -> Vec<(MoveSequence, i32)> {
+    let mut scores = state
+        .list_valid_moves()
+        .into_iter()
+        .filter_map(|sequence| {
+            let mut next = state.clone();
+            next.apply_sequence(&sequence.0).ok()?;
+
+            let score = -negamax(&next, depth.saturating_sub(1), i32::MIN + 1, i32::MAX);
+            Some((sequence, score))
+        })
+        .collect::<Vec<_>>();
+
+    scores.sort_by_key(|(_, score)| -score);
+    scores
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate_moves, search, DEPTH, MAN_VALUE};
+    use crate::game::{
+        empty_board, Board, GameState, GameStatus, Move, Piece, PieceType, Player, Variant,
+    };
+
+    fn man(player: Player) -> Option<Piece> {
+        Some(Piece {
+            type_: PieceType::Man,
+            player,
+        })
+    }
+
+    fn state(board: Board, current_player: Player) -> GameState {
+        GameState::from_position(
+            Variant::International,
+            board,
+            current_player,
+            GameStatus::Running,
+        )
+    }
+
+    #[test]
+    fn plays_legal_move() {
+        let game = GameState::default();
+        let (moves, _) = search(&game, DEPTH).unwrap();
+
+        assert!(game.list_valid_moves().iter().any(|m| m.0 == moves));
+    }
+
+    #[test]
+    fn prefers_the_longest_capture() {
+        let mut board = empty_board(10);
+        board[6][1] = man(Player::White);
+        board[5][2] = man(Player::Black);
+        board[3][4] = man(Player::Black);
+        board[0][9] = man(Player::Black);
+
+        let (moves, _) = search(&state(board, Player::White), DEPTH).unwrap();
+
+        assert_eq!(
+            moves,
+            vec![
+                Move {
+                    from: (6, 1),
+                    to: (4, 3)
+                },
+                Move {
+                    from: (4, 3),
+                    to: (2, 5)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cannot_move_without_pieces() {
+        let mut board = empty_board(10);
+        board[0][1] = man(Player::Black);
+
+        assert_eq!(search(&state(board, Player::White), DEPTH), None);
+    }
+
+    #[test]
+    fn evaluates_every_move_best_first() {
+        let mut board = empty_board(10);
+        board[6][1] = man(Player::White);
+        board[6][7] = man(Player::White);
+        board[4][5] = man(Player::Black);
+        board[0][9] = man(Player::Black);
+        let game = state(board, Player::White);
+
+        let scores = evaluate_moves(&game, DEPTH);
+
+        assert_eq!(scores.len(), game.list_valid_moves().len());
+        assert!(scores.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert_eq!(
+            Some((scores[0].0 .0.clone(), scores[0].1)),
+            search(&game, DEPTH)
+        );
+    }
+
+    #[test]
+    fn scores_losing_a_man() {
+        let mut board = empty_board(10);
+        board[6][3] = man(Player::White);
+        board[6][9] = man(Player::White);
+        board[4][5] = man(Player::Black);
+        board[0][1] = man(Player::Black);
+
+        let scores = evaluate_moves(&state(board, Player::White), 2);
+        let score = |from, to| {
+            scores
+                .iter()
+                .find(|(s, _)| s.0 == vec![Move { from, to }])
+                .unwrap()
+                .1
+        };
+
+        // Moving next to the black man lets it capture.
+        assert_eq!(score((6, 9), (5, 8)), 0);
+        assert_eq!(score((6, 3), (5, 4)), -MAN_VALUE);
+    }
+}
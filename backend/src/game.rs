@@ -1,17 +1,28 @@
-use crate::api::Error;
+use crate::{api::Error, clock::Clock};
+use rocket::FromFormField;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{Display, Write},
-    ops::{Add, Div, Mul},
+    ops::{Add, Mul},
 };
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum Player {
     White,
     Black,
 }
 
+impl Player {
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::White => Player::Black,
+            Player::Black => Player::White,
+        }
+    }
+}
+
 impl Display for Player {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_char(match self {
@@ -21,7 +32,7 @@ impl Display for Player {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum PieceType {
     Man,
@@ -37,7 +48,7 @@ impl Display for PieceType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Piece {
     #[serde(rename = "type")]
     pub type_: PieceType,
@@ -50,15 +61,166 @@ impl Display for Piece {
     }
 }
 
-const BOARD_SIZE: usize = 10;
+#[derive(
+    Serialize, Deserialize, FromFormField, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Variant {
+    /// International draughts on a 10×10 board.
+    #[default]
+    International,
+    /// English draughts, or American checkers, on an 8×8 board.
+    English,
+    /// Russian draughts on an 8×8 board.
+    Russian,
+    /// Brazilian draughts: the international rules on an 8×8 board.
+    Brazilian,
+    /// Frisian draughts on a 10×10 board.
+    Frisian,
+    /// Turkish draughts on every square of an 8×8 board.
+    Turkish,
+    /// Canadian draughts: the international rules on a 12×12 board.
+    Canadian,
+}
+
+/// Lines along which pieces move or capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directions {
+    Diagonal,
+    /// Along rows and columns. On the dark squares, the orthogonal neighbours
+    /// of a square are two squares away.
+    Orthogonal,
+    Both,
+}
+
+/// Which capture must be played when there are several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapturePriority {
+    /// Any of them.
+    Free,
+    /// One taking the most pieces.
+    Count,
+    /// One taking the most valuable pieces, a king being worth one and a half
+    /// men, and with a king when it is a tie.
+    Value,
+}
+
+/// The rules of a variant, on which the moves depend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    /// Number of rows and columns of the board.
+    pub size: usize,
+    /// Whether pieces stand on every square, rather than only on the dark
+    /// ones.
+    pub all_squares: bool,
+    /// First row filled with men at the start, counted from each side.
+    pub first_row: usize,
+    /// Number of rows filled with men at the start, on each side.
+    pub rows: usize,
+    pub first_player: Player,
+    pub moves: Directions,
+    pub captures: Directions,
+    /// Whether kings move any number of squares, rather than a single one.
+    pub flying_kings: bool,
+    /// Whether men capture backwards as well as forwards.
+    pub men_capture_backwards: bool,
+    pub capture_priority: CapturePriority,
+    /// Whether a man reaching the last row in the middle of a capture is
+    /// promoted and continues capturing as a king, rather than as a man.
+    pub promotes_during_capture: bool,
+    /// Whether captured pieces are removed as soon as they are jumped over,
+    /// rather than at the end of the move.
+    pub removes_immediately: bool,
+}
+
+impl Variant {
+    pub fn rules(self) -> Rules {
+        match self {
+            Variant::International => Rules {
+                size: 10,
+                all_squares: false,
+                first_row: 0,
+                rows: 4,
+                first_player: Player::White,
+                moves: Directions::Diagonal,
+                captures: Directions::Diagonal,
+                flying_kings: true,
+                men_capture_backwards: true,
+                capture_priority: CapturePriority::Count,
+                promotes_during_capture: false,
+                removes_immediately: false,
+            },
+            Variant::English => Rules {
+                size: 8,
+                rows: 3,
+                first_player: Player::Black,
+                flying_kings: false,
+                men_capture_backwards: false,
+                capture_priority: CapturePriority::Free,
+                ..Variant::International.rules()
+            },
+            Variant::Russian => Rules {
+                size: 8,
+                rows: 3,
+                capture_priority: CapturePriority::Free,
+                promotes_during_capture: true,
+                ..Variant::International.rules()
+            },
+            Variant::Brazilian => Rules {
+                size: 8,
+                rows: 3,
+                ..Variant::International.rules()
+            },
+            Variant::Frisian => Rules {
+                captures: Directions::Both,
+                capture_priority: CapturePriority::Value,
+                ..Variant::International.rules()
+            },
+            Variant::Turkish => Rules {
+                size: 8,
+                all_squares: true,
+                first_row: 1,
+                rows: 2,
+                moves: Directions::Orthogonal,
+                captures: Directions::Orthogonal,
+                men_capture_backwards: false,
+                removes_immediately: true,
+                ..Variant::International.rules()
+            },
+            Variant::Canadian => Rules {
+                size: 12,
+                rows: 5,
+                ..Variant::International.rules()
+            },
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Variant::International => "international",
+            Variant::English => "english",
+            Variant::Russian => "russian",
+            Variant::Brazilian => "brazilian",
+            Variant::Frisian => "frisian",
+            Variant::Turkish => "turkish",
+            Variant::Canadian => "canadian",
+        })
+    }
+}
+
+pub type Board = Vec<Vec<Option<Piece>>>;
 
-pub type Board = [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE];
+pub fn empty_board(size: usize) -> Board {
+    vec![vec![None; size]; size]
+}
 
-fn default_board() -> Board {
-    fn fill_row(board: &mut Board, row: usize, player: Player) {
-        for i in 0..BOARD_SIZE {
-            if (i + row) % 2 == 1 {
-                board[row][i] = Some(Piece {
+fn default_board(rules: Rules) -> Board {
+    fn fill_row(board: &mut Board, row: usize, player: Player, all_squares: bool) {
+        for (i, cell) in board[row].iter_mut().enumerate() {
+            if all_squares || (i + row) % 2 == 1 {
+                *cell = Some(Piece {
                     type_: PieceType::Man,
                     player,
                 })
@@ -66,63 +228,196 @@ fn default_board() -> Board {
         }
     }
 
-    let mut board: Board = Board::default();
+    let mut board = empty_board(rules.size);
 
-    fill_row(&mut board, 0, Player::Black);
-    fill_row(&mut board, 1, Player::Black);
-    fill_row(&mut board, 2, Player::Black);
-    fill_row(&mut board, 3, Player::Black);
-
-    fill_row(&mut board, BOARD_SIZE - 4, Player::White);
-    fill_row(&mut board, BOARD_SIZE - 3, Player::White);
-    fill_row(&mut board, BOARD_SIZE - 2, Player::White);
-    fill_row(&mut board, BOARD_SIZE - 1, Player::White);
+    for row in rules.first_row..rules.first_row + rules.rows {
+        fill_row(&mut board, row, Player::Black, rules.all_squares);
+        fill_row(
+            &mut board,
+            rules.size - 1 - row,
+            Player::White,
+            rules.all_squares,
+        );
+    }
 
     board
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, ToSchema)]
 pub struct TurnStatus {
+    /// Id of the game.
+    pub id: u64,
     pub game: GameState,
+    pub clock: Option<Clock>,
     pub ai_output: String,
+    /// Job playing the AI's turn, if any.
+    pub job: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[serde(tag = "status", content = "player", rename_all = "camelCase")]
 pub enum GameStatus {
     Running,
     Draw,
     Victory(Player),
+    /// The opponent of the player ran out of time.
+    VictoryOnTime(Player),
 }
 
-#[derive(Debug, Serialize, Clone)]
+impl GameStatus {
+    pub fn winner(&self) -> Option<Player> {
+        match self {
+            GameStatus::Victory(player) | GameStatus::VictoryOnTime(player) => Some(*player),
+            GameStatus::Running | GameStatus::Draw => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(from = "SerializedGameState")]
 pub struct GameState {
+    /// Rows of the board, from black's side to white's side.
+    #[schema(value_type = Vec<Vec<Option<Piece>>>)]
     pub board: Board,
     pub current_player: Player,
     pub status: GameStatus,
+    pub variant: Variant,
+    /// Zobrist hash of the position, kept up to date by `apply_sequence` and
+    /// `undo`.
+    #[serde(skip)]
+    key: u64,
+}
+
+/// A `GameState` as serialized, without its key.
+#[derive(Deserialize)]
+struct SerializedGameState {
+    board: Board,
+    current_player: Player,
+    status: GameStatus,
+    #[serde(default)]
+    variant: Variant,
+}
+
+impl From<SerializedGameState> for GameState {
+    fn from(state: SerializedGameState) -> Self {
+        Self::from_position(
+            state.variant,
+            state.board,
+            state.current_player,
+            state.status,
+        )
+    }
+}
+
+/// What a sequence of moves changed, for `GameState::undo` to restore it.
+#[derive(Debug, Clone)]
+pub struct Undo {
+    from: Position,
+    to: Position,
+    piece: Piece,
+    captured: Vec<(Position, Piece)>,
+    status: GameStatus,
+}
+
+/// Mixes the bits of `x`, as SplitMix64 does. Unlike a seeded random number
+/// generator, it gives the same keys across versions of the server.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Zobrist key of a piece on a square.
+fn piece_key((x, y): Position, piece: &Piece) -> u64 {
+    mix((x as u64) << 32 | (y as u64) << 16 | (piece.type_ as u64) << 1 | piece.player as u64)
+}
+
+/// Zobrist key of black being to move.
+fn black_key() -> u64 {
+    mix(1 << 63)
+}
+
+fn variant_key(variant: Variant) -> u64 {
+    mix(1 << 62 | variant as u64)
+}
+
+impl GameState {
+    pub fn new(variant: Variant) -> Self {
+        let rules = variant.rules();
+
+        Self::from_position(
+            variant,
+            default_board(rules),
+            rules.first_player,
+            GameStatus::Running,
+        )
+    }
+
+    pub fn from_position(
+        variant: Variant,
+        board: Board,
+        current_player: Player,
+        status: GameStatus,
+    ) -> Self {
+        let mut state = Self {
+            board,
+            current_player,
+            status,
+            variant,
+            key: 0,
+        };
+        state.key = state.compute_key();
+
+        state
+    }
+
+    /// Identifies the position: the variant, the pieces on the board and the
+    /// player to move.
+    pub fn position_key(&self) -> u64 {
+        self.key
+    }
+
+    fn compute_key(&self) -> u64 {
+        let mut key = variant_key(self.variant);
+        if self.current_player == Player::Black {
+            key ^= black_key();
+        }
+
+        for (x, row) in self.board.iter().enumerate() {
+            for (y, piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    key ^= piece_key((x, y), piece);
+                }
+            }
+        }
+
+        key
+    }
 }
 
 impl Default for GameState {
     fn default() -> Self {
-        Self {
-            board: default_board(),
-            current_player: Player::White,
-            status: GameStatus::Running,
-        }
+        Self::new(Variant::default())
     }
 }
 
 pub type Position = (usize, usize);
 pub type MoveSequence = (Vec<Move>, Vec<Position>);
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, ToSchema)]
 pub struct Move {
+    /// `[row, column]`
+    #[schema(value_type = [usize])]
     pub from: Position,
+    /// `[row, column]`
+    #[schema(value_type = [usize])]
     pub to: Position,
 }
 
-fn is_valid_pos(pos: Pos) -> bool {
-    0 <= pos.x && pos.x < BOARD_SIZE as i32 && 0 <= pos.y && pos.y < BOARD_SIZE as i32
+fn is_valid_pos(board: &Board, pos: Pos) -> bool {
+    let size = board.len() as i32;
+    0 <= pos.x && pos.x < size && 0 <= pos.y && pos.y < size
 }
 
 fn at(board: &Board, pos: Pos) -> Option<&Piece> {
@@ -156,19 +451,24 @@ impl Mul<i32> for Pos {
         }
     }
 }
-impl Div<i32> for Pos {
-    type Output = Pos;
 
-    fn div(self, rhs: i32) -> Self::Output {
-        Pos {
-            x: self.x / rhs,
-            y: self.y / rhs,
-        }
-    }
+const fn p(x: i32, y: i32) -> Pos {
+    Pos { x, y }
 }
 
-fn p(x: i32, y: i32) -> Pos {
-    Pos { x, y }
+const DIAGONALS: [Pos; 4] = [p(1, 1), p(1, -1), p(-1, 1), p(-1, -1)];
+const ORTHOGONALS: [Pos; 4] = [p(1, 0), p(-1, 0), p(0, 1), p(0, -1)];
+
+/// A move in the making.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Intermediate {
+    pos: Pos,
+    captures: Vec<Pos>,
+    moves: Vec<Move>,
+}
+
+fn pos(position: Position) -> Pos {
+    p(position.0 as i32, position.1 as i32)
 }
 
 fn mov(from: Pos, to: Pos) -> Move {
@@ -197,40 +497,85 @@ impl GameState {
             + "\n"
     }
 
-    pub fn apply_sequence(&mut self, seq: &[Move]) -> Result<(), Error> {
+    pub fn apply_sequence(&mut self, seq: &[Move]) -> Result<Undo, Error> {
         let available_moves = self.list_valid_moves();
 
-        let move_ = available_moves.into_iter().find(|m| m.0 == seq);
+        let move_ = available_moves.iter().find(|m| m.0 == seq).cloned();
 
         if let Some((moves, captures)) = move_ {
             let from = moves.first().unwrap().from;
             let to = moves.last().unwrap().to;
 
-            self.board[to.0][to.1] = self.board[from.0][from.1].take();
-            if to.0
-                == match self.current_player {
-                    Player::White => 0,
-                    Player::Black => BOARD_SIZE - 1,
-                }
-            {
-                self.board[to.0][to.1].as_mut().unwrap().type_ = PieceType::King;
-                // TODO Test
+            // A man is promoted when its move ends on the last row. Reaching
+            // it in the middle of a capture is not enough, unless the rules
+            // promote it there, in which case it went on capturing as a king.
+            let last_row = self.last_row() as usize;
+            let promoted = if self.variant.rules().promotes_during_capture {
+                moves.iter().any(|m| m.to.0 == last_row)
+            } else {
+                to.0 == last_row
+            };
+
+            let piece = self.board[from.0][from.1].take().unwrap();
+            self.key ^= piece_key(from, &piece);
+
+            let captured = captures
+                .into_iter()
+                .map(|c| (c, self.board[c.0][c.1].take().unwrap()))
+                .collect::<Vec<_>>();
+            for (c, captured) in &captured {
+                self.key ^= piece_key(*c, captured);
             }
 
-            for captured in captures {
-                self.board[captured.0][captured.1] = None;
+            let mut moved = piece.clone();
+            if promoted {
+                moved.type_ = PieceType::King;
             }
+            self.key ^= piece_key(to, &moved);
+            self.board[to.0][to.1] = Some(moved);
+
+            let undo = Undo {
+                from,
+                to,
+                piece,
+                captured,
+                status: self.status.clone(),
+            };
 
             self.status = self.compute_status();
-            self.current_player = match self.current_player {
-                Player::White => Player::Black,
-                Player::Black => Player::White,
-            };
+            self.current_player = self.current_player.opponent();
+            self.key ^= black_key();
+
+            // A player who cannot move loses.
+            if matches!(self.status, GameStatus::Running) && self.list_valid_moves().is_empty() {
+                self.status = GameStatus::Victory(self.current_player.opponent());
+            }
 
-            Ok(())
+            Ok(undo)
         } else {
-            Err(Error::InvalidMove)
+            Err(Error::InvalidMove {
+                move_: seq.to_vec(),
+                legal_moves: available_moves.into_iter().map(|m| m.0).collect(),
+            })
+        }
+    }
+
+    /// Restores the position before the sequence of moves that returned
+    /// `undo`, which must be the last one applied.
+    pub fn undo(&mut self, undo: Undo) {
+        self.current_player = self.current_player.opponent();
+        self.key ^= black_key();
+        self.status = undo.status;
+
+        if let Some(moved) = self.board[undo.to.0][undo.to.1].take() {
+            self.key ^= piece_key(undo.to, &moved);
         }
+        for (c, captured) in undo.captured {
+            self.key ^= piece_key(c, &captured);
+            self.board[c.0][c.1] = Some(captured);
+        }
+        self.key ^= piece_key(undo.from, &undo.piece);
+        self.board[undo.from.0][undo.from.1] = Some(undo.piece);
     }
 
     fn compute_status(&self) -> GameStatus {
@@ -261,219 +606,226 @@ impl GameState {
         }
     }
 
-    pub fn list_valid_moves(&self) -> Vec<MoveSequence> {
-        #[derive(Debug, Clone, PartialEq, Eq)]
-        struct Intermediate {
-            pos: Pos,
-            captures: Vec<Pos>,
-            moves: Vec<Move>,
-        }
-
-        fn update_intermediate(
-            new_pos: Pos,
-            captured_pos: Option<Pos>,
-            mut i: Intermediate,
-        ) -> Option<Intermediate> {
-            if captured_pos.is_some_and(|c| i.captures.contains(&c)) {
-                None
-            } else {
-                i.moves.push(mov(i.pos, new_pos));
-                i.pos = new_pos;
+    fn forward(&self) -> i32 {
+        match self.current_player {
+            Player::White => -1,
+            Player::Black => 1,
+        }
+    }
 
-                if let Some(captured_pos) = captured_pos {
-                    i.captures.push(captured_pos);
-                }
+    fn last_row(&self) -> i32 {
+        match self.current_player {
+            Player::White => 0,
+            Player::Black => self.board.len() as i32 - 1,
+        }
+    }
 
-                Some(i)
-            }
+    /// Whether the square is empty during the capture `i`, the piece having
+    /// left its starting square.
+    fn is_empty(&self, i: &Intermediate, pos: Pos) -> bool {
+        is_valid_pos(&self.board, pos)
+            && (at(&self.board, pos).is_none()
+                || i.moves
+                    .first()
+                    .is_some_and(|m| m.from == (pos.x as usize, pos.y as usize))
+                || self.variant.rules().removes_immediately && i.captures.contains(&pos))
+    }
+
+    /// Unless they are removed immediately, captured pieces stay on the board
+    /// until the end of the move, and cannot be jumped over twice.
+    fn is_capturable(&self, i: &Intermediate, pos: Pos) -> bool {
+        is_valid_pos(&self.board, pos)
+            && at(&self.board, pos).is_some_and(|p| p.player != self.current_player)
+            && !i.captures.contains(&pos)
+    }
+
+    fn directions(&self, type_: PieceType, capture: bool) -> Vec<Pos> {
+        let rules = self.variant.rules();
+        let step = if rules.all_squares { 1 } else { 2 };
+
+        let mut directions = vec![];
+        let lines = if capture { rules.captures } else { rules.moves };
+        if lines != Directions::Orthogonal {
+            directions.extend(DIAGONALS);
+        }
+        if lines != Directions::Diagonal {
+            directions.extend(ORTHOGONALS.map(|d| d * step));
         }
 
-        fn list_valid_moves_for_man(state: &GameState, i: Intermediate) -> Vec<Intermediate> {
-            let is_valid_capture_move = |d: Pos| {
-                let new_pos = i.pos + d;
-                let captured_pos = i.pos + d / 2;
+        // Men never move backwards.
+        if matches!(type_, PieceType::Man) && !(capture && rules.men_capture_backwards) {
+            directions.retain(|d| d.x * self.forward() >= 0);
+        }
 
-                is_valid_pos(new_pos)
-                    && at(&state.board, new_pos).is_none()
-                    && at(&state.board, captured_pos)
-                        .is_some_and(|p| p.player != state.current_player)
-            };
+        directions
+    }
 
-            let mut moves: Vec<Intermediate> = if i.captures.is_empty() {
-                let dv = match state.current_player {
-                    Player::White => -1,
-                    Player::Black => 1,
-                };
+    /// How many squares the piece moves at most.
+    fn range(&self, type_: PieceType) -> i32 {
+        match type_ {
+            PieceType::King if self.variant.rules().flying_kings => self.board.len() as i32,
+            _ => 1,
+        }
+    }
 
-                let d = vec![p(dv, 1), p(dv, -1)];
-
-                d.into_iter()
-                    .filter(|d| is_valid_pos(i.pos + *d))
-                    .flat_map(|d| {
-                        if at(&state.board, i.pos + d).is_none() {
-                            vec![Intermediate {
-                                pos: i.pos + d,
-                                captures: vec![],
-                                moves: vec![mov(i.pos, i.pos + d)],
-                            }]
-                        } else if is_valid_capture_move(d * 2) {
-                            list_valid_moves_for_man(
-                                state,
-                                Intermediate {
-                                    pos: i.pos + d * 2,
-                                    captures: vec![i.pos + d],
-                                    moves: vec![mov(i.pos, i.pos + d * 2)],
-                                },
-                            )
-                        } else {
-                            vec![]
-                        }
-                    })
-                    .collect()
-            } else {
-                let d = vec![p(2, 2), p(2, -2), p(-2, 2), p(-2, -2)];
-
-                d.into_iter()
-                    .filter(|d| (is_valid_capture_move(*(d))))
-                    .filter_map(|d| {
-                        let new_pos = i.pos + d;
-                        let captured_pos = i.pos + d / 2;
-                        update_intermediate(new_pos, Some(captured_pos), i.clone())
+    fn list_simple_moves(&self, pos: Pos, type_: PieceType) -> Vec<Intermediate> {
+        let mut moves = vec![];
+
+        for d in self.directions(type_, false) {
+            moves.extend(
+                (1..=self.range(type_))
+                    .map(|distance| pos + d * distance)
+                    .take_while(|to| {
+                        is_valid_pos(&self.board, *to) && at(&self.board, *to).is_none()
                     })
-                    .flat_map(|i| list_valid_moves_for_man(state, i))
-                    .collect()
-            };
+                    .map(|to| Intermediate {
+                        pos: to,
+                        captures: vec![],
+                        moves: vec![mov(pos, to)],
+                    }),
+            );
+        }
+
+        moves
+    }
 
-            if !i.moves.is_empty() {
-                moves.push(i);
+    /// The captured piece and the landing square of each single capture.
+    fn list_capture_steps(&self, i: &Intermediate, type_: PieceType) -> Vec<(Pos, Pos)> {
+        let range = self.range(type_);
+        let mut steps = vec![];
+
+        // When captured pieces are removed immediately, a piece cannot go back
+        // the way it came.
+        let back = i
+            .moves
+            .last()
+            .filter(|_| self.variant.rules().removes_immediately)
+            .map(|m| {
+                let d = pos(m.from) + pos(m.to) * -1;
+                p(d.x.signum(), d.y.signum())
+            });
+
+        for d in self.directions(type_, true) {
+            if back == Some(p(d.x.signum(), d.y.signum())) {
+                continue;
             }
 
-            moves
+            let Some(captured) = (1..=range)
+                .map(|distance| i.pos + d * distance)
+                .find(|pos| !self.is_empty(i, *pos))
+                .filter(|pos| self.is_capturable(i, *pos))
+            else {
+                continue;
+            };
+
+            steps.extend(
+                (1..=range)
+                    .map(|distance| captured + d * distance)
+                    .take_while(|to| self.is_empty(i, *to))
+                    .map(|to| (captured, to)),
+            );
         }
 
-        fn list_valid_moves_for_king(state: &GameState, i: Intermediate) -> Vec<Intermediate> {
-            let d = vec![p(1, 1), p(1, -1), p(-1, 1), p(-1, -1)];
-            let must_capture = !i.captures.is_empty();
+        steps
+    }
 
-            let mut moves: Vec<Intermediate> = d
-                .into_iter()
-                .flat_map(|delta| {
-                    let next = (1..BOARD_SIZE).fold(
-                        (None, vec![], false),
-                        |(capturable, moves, done), distance| {
-                            let pos = i.pos + delta * distance as i32;
-
-                            if done || !is_valid_pos(pos) {
-                                return (capturable, moves, true);
-                            }
-
-                            match at(&state.board, pos) {
-                                Some(p) if p.player == state.current_player => {
-                                    (capturable, moves, true)
-                                }
-                                Some(_) => (Some(pos), moves, capturable.is_some()),
-                                None => {
-                                    if !must_capture || capturable.is_some() {
-                                        let mut new_moves = moves.clone();
-                                        new_moves.push((pos, capturable));
-                                        (capturable, new_moves, done)
-                                    } else {
-                                        (capturable, moves, done)
-                                    }
-                                }
-                            }
-                        },
-                    );
+    /// Every complete capture continuing `i`: a capture cannot be stopped while
+    /// another piece can be taken.
+    fn list_captures(&self, i: Intermediate, type_: PieceType) -> Vec<Intermediate> {
+        let promoted = matches!(type_, PieceType::Man)
+            && self.variant.rules().promotes_during_capture
+            && !i.captures.is_empty()
+            && i.pos.x == self.last_row();
+        let type_ = if promoted { PieceType::King } else { type_ };
 
-                    next.1
-                })
-                .flat_map(|(new_pos, captures)| {
-                    if let Some(i) = update_intermediate(new_pos, captures, i.clone()) {
-                        if captures.is_some() {
-                            list_valid_moves_for_king(state, i)
-                        } else {
-                            vec![i]
-                        }
-                    } else {
-                        vec![]
-                    }
-                })
-                .collect::<Vec<_>>();
+        let mut captures = vec![];
 
-            if !i.moves.is_empty() {
-                moves.push(i);
-            }
+        for (captured, to) in self.list_capture_steps(&i, type_) {
+            let mut next = i.clone();
+            next.moves.push(mov(i.pos, to));
+            next.pos = to;
+            next.captures.push(captured);
 
-            moves
+            captures.append(&mut self.list_captures(next, type_));
         }
 
-        let mut available_moves = vec![];
+        if captures.is_empty() && !i.captures.is_empty() {
+            vec![i]
+        } else {
+            captures
+        }
+    }
 
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
-                let pos = p(col as i32, row as i32);
-                let piece = at(&self.board, pos);
+    pub fn list_valid_moves(&self) -> Vec<MoveSequence> {
+        let mut captures = vec![];
+        let mut simple_moves = vec![];
 
-                let moves = match piece {
-                    Some(Piece {
-                        type_: PieceType::Man,
-                        player,
-                    }) if *player == self.current_player => list_valid_moves_for_man(
-                        self,
-                        Intermediate {
-                            pos,
-                            captures: vec![],
-                            moves: vec![],
-                        },
-                    ),
-                    Some(Piece {
-                        type_: PieceType::King,
-                        player,
-                    }) if *player == self.current_player => list_valid_moves_for_king(
-                        self,
-                        Intermediate {
-                            pos,
-                            captures: vec![],
-                            moves: vec![],
-                        },
-                    ),
-                    _ => continue,
+        for (x, row) in self.board.iter().enumerate() {
+            for (y, piece) in row.iter().enumerate() {
+                let Some(piece) = piece.as_ref().filter(|p| p.player == self.current_player) else {
+                    continue;
                 };
-
-                available_moves.append(
-                    &mut moves
-                        .into_iter()
-                        .map(|i| {
-                            (
-                                i.moves,
-                                i.captures
-                                    .into_iter()
-                                    .map(|c| (c.x as usize, c.y as usize))
-                                    .collect::<Vec<_>>(),
-                            )
-                        })
-                        .collect(),
-                );
+                let pos = p(x as i32, y as i32);
+
+                captures.append(&mut self.list_captures(
+                    Intermediate {
+                        pos,
+                        captures: vec![],
+                        moves: vec![],
+                    },
+                    piece.type_,
+                ));
+                simple_moves.append(&mut self.list_simple_moves(pos, piece.type_));
             }
         }
 
-        let max = available_moves.iter().map(|m| m.1.len()).max();
+        let priority = |i: &Intermediate| match self.variant.rules().capture_priority {
+            CapturePriority::Free => (0, false),
+            CapturePriority::Count => (i.captures.len(), false),
+            CapturePriority::Value => (
+                i.captures
+                    .iter()
+                    .map(|c| match at(&self.board, *c).unwrap().type_ {
+                        PieceType::Man => 2,
+                        PieceType::King => 3,
+                    })
+                    .sum(),
+                matches!(
+                    at(&self.board, pos(i.moves[0].from)).unwrap().type_,
+                    PieceType::King
+                ),
+            ),
+        };
+        let max = captures.iter().map(priority).max();
+        captures.retain(|i| Some(priority(i)) == max);
 
-        if let Some(max) = max {
-            available_moves
-                .into_iter()
-                .filter(|m| m.1.len() == max)
-                .collect()
-        } else {
-            vec![]
+        // Capturing is mandatory.
+        if captures.is_empty() {
+            captures = simple_moves;
         }
+
+        captures
+            .into_iter()
+            .map(|i| {
+                (
+                    i.moves,
+                    i.captures
+                        .into_iter()
+                        .map(|c| (c.x as usize, c.y as usize))
+                        .collect(),
+                )
+            })
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Board, GameState, Move, MoveSequence, Piece};
+    use super::{
+        empty_board, Board, GameState, GameStatus, Move, MoveSequence, Piece, Player, Variant,
+    };
     use core::hash::Hash;
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
     use std::collections::HashSet;
 
     fn p(type_: char, player: char) -> Option<Piece> {
@@ -498,20 +850,14 @@ mod test {
         }
     }
 
-    fn list(board: Board) -> (Vec<MoveSequence>, Vec<MoveSequence>) {
+    fn board(rows: [[Option<Piece>; 10]; 10]) -> Board {
+        rows.into_iter().map(Vec::from).collect()
+    }
+
+    fn list(rows: [[Option<Piece>; 10]; 10]) -> (Vec<MoveSequence>, Vec<MoveSequence>) {
         (
-            GameState {
-                board: board.clone(),
-                current_player: crate::game::Player::White,
-                status: super::GameStatus::Running,
-            }
-            .list_valid_moves(),
-            GameState {
-                board,
-                current_player: crate::game::Player::Black,
-                status: super::GameStatus::Running,
-            }
-            .list_valid_moves(),
+            state(Variant::International, board(rows.clone()), 'W').list_valid_moves(),
+            state(Variant::International, board(rows), 'B').list_valid_moves(),
         )
     }
 
@@ -525,8 +871,9 @@ mod test {
 
     #[test]
     fn empty() {
-        let state = GameState {
-            board: [
+        let state = state(
+            Variant::International,
+            board([
                 [None, None, None, None, None, None, None, None, None, None],
                 [None, None, None, None, None, None, None, None, None, None],
                 [None, None, None, None, None, None, None, None, None, None],
@@ -537,10 +884,9 @@ mod test {
                 [None, None, None, None, None, None, None, None, None, None],
                 [None, None, None, None, None, None, None, None, None, None],
                 [None, None, None, None, None, None, None, None, None, None],
-            ],
-            current_player: super::Player::White,
-            ..Default::default()
-        };
+            ]),
+            'W',
+        );
         assert!(state.list_valid_moves().is_empty());
     }
 
@@ -856,4 +1202,539 @@ mod test {
 
         assert!(black_moves.is_empty());
     }
+
+    fn state(variant: Variant, board: Board, player: char) -> GameState {
+        GameState::from_position(
+            variant,
+            board,
+            p('M', player).unwrap().player,
+            GameStatus::Running,
+        )
+    }
+
+    #[test]
+    fn english_setup() {
+        let state = GameState::new(Variant::English);
+
+        assert_eq!(state.board.len(), 8);
+        assert_eq!(state.board.iter().flatten().flatten().count(), 24);
+        assert_eq!(state.current_player, super::Player::Black);
+        assert_eq!(state.list_valid_moves().len(), 7);
+    }
+
+    #[test]
+    fn english_man_captures_forward_only() {
+        let mut board = empty_board(8);
+        board[4][3] = p('M', 'W');
+        board[5][2] = p('M', 'B');
+
+        assert!(iters_equal_anyorder(
+            state(Variant::English, board.clone(), 'W')
+                .list_valid_moves()
+                .iter(),
+            [(vec![m(4, 3, 3, 2)], vec![]), (vec![m(4, 3, 3, 4)], vec![])].iter()
+        ));
+
+        board[3][4] = p('M', 'B');
+        assert_eq!(
+            state(Variant::English, board, 'W').list_valid_moves(),
+            vec![(vec![m(4, 3, 2, 5)], vec![(3, 4)])]
+        );
+    }
+
+    #[test]
+    fn english_short_king() {
+        let mut board = empty_board(8);
+        board[4][3] = p('K', 'W');
+        board[2][1] = p('M', 'B');
+
+        assert_eq!(
+            state(Variant::English, board.clone(), 'W')
+                .list_valid_moves()
+                .len(),
+            4
+        );
+
+        board[5][4] = p('M', 'B');
+        assert_eq!(
+            state(Variant::English, board, 'W').list_valid_moves(),
+            vec![(vec![m(4, 3, 6, 5)], vec![(5, 4)])]
+        );
+    }
+
+    #[test]
+    fn english_free_capture_choice() {
+        let mut board = empty_board(8);
+        board[7][0] = p('M', 'W');
+        board[6][1] = p('M', 'B');
+        board[4][3] = p('M', 'B');
+        board[5][6] = p('M', 'W');
+        board[4][5] = p('M', 'B');
+
+        let moves = state(Variant::English, board, 'W').list_valid_moves();
+
+        assert_eq!(moves.len(), 2);
+        assert!(iters_equal_anyorder(
+            moves.iter(),
+            [
+                (vec![m(7, 0, 5, 2), m(5, 2, 3, 4)], vec![(6, 1), (4, 3)]),
+                (vec![m(5, 6, 3, 4)], vec![(4, 5)]),
+            ]
+            .iter()
+        ));
+    }
+
+    #[test]
+    fn english_promotion() {
+        let mut board = empty_board(8);
+        board[2][1] = p('M', 'W');
+        board[1][2] = p('M', 'B');
+        board[1][4] = p('M', 'B');
+
+        let mut state = state(Variant::English, board, 'W');
+        let moves = state.list_valid_moves();
+
+        assert_eq!(moves, vec![(vec![m(2, 1, 0, 3)], vec![(1, 2)])]);
+
+        state.apply_sequence(&moves[0].0).unwrap();
+        assert!(matches!(
+            state.board[0][3].as_ref().unwrap().type_,
+            super::PieceType::King
+        ));
+    }
+
+    #[test]
+    fn international_man_captures_backwards() {
+        let mut board = empty_board(10);
+        board[4][3] = p('M', 'W');
+        board[5][2] = p('M', 'B');
+
+        let state = state(Variant::International, board, 'W');
+
+        assert_eq!(
+            state.list_valid_moves(),
+            vec![(vec![m(4, 3, 6, 1)], vec![(5, 2)])]
+        );
+    }
+
+    #[test]
+    fn blocked_player_loses() {
+        let mut board = empty_board(8);
+        board[0][1] = p('M', 'B');
+        board[1][0] = p('M', 'W');
+        board[3][2] = p('M', 'W');
+        board[1][2] = p('M', 'W');
+
+        let mut state = state(Variant::English, board, 'W');
+        state.apply_sequence(&[m(3, 2, 2, 3)]).unwrap();
+
+        assert!(matches!(
+            state.status,
+            GameStatus::Victory(super::Player::White)
+        ));
+    }
+
+    #[test]
+    fn russian_promotes_during_capture() {
+        let mut board = empty_board(8);
+        board[2][1] = p('M', 'W');
+        board[1][2] = p('M', 'B');
+        board[1][4] = p('M', 'B');
+
+        let mut state = state(Variant::Russian, board, 'W');
+        let moves = state.list_valid_moves();
+
+        assert!(iters_equal_anyorder(
+            moves.iter(),
+            [5, 6, 7]
+                .map(|y| (vec![m(2, 1, 0, 3), m(0, 3, y - 3, y)], vec![(1, 2), (1, 4)]))
+                .iter()
+        ));
+        assert_eq!(moves.len(), 3);
+
+        state.apply_sequence(&moves[0].0).unwrap();
+        let (x, y) = moves[0].0[1].to;
+        assert!(matches!(
+            state.board[x][y].as_ref().unwrap().type_,
+            super::PieceType::King
+        ));
+    }
+
+    #[test]
+    fn brazilian_captures_through_last_row_as_man() {
+        let mut board = empty_board(8);
+        board[2][1] = p('M', 'W');
+        board[1][2] = p('M', 'B');
+        board[1][4] = p('M', 'B');
+
+        let mut state = state(Variant::Brazilian, board, 'W');
+        let moves = state.list_valid_moves();
+
+        assert_eq!(
+            moves,
+            vec![(vec![m(2, 1, 0, 3), m(0, 3, 2, 5)], vec![(1, 2), (1, 4)])]
+        );
+
+        state.apply_sequence(&moves[0].0).unwrap();
+        assert!(matches!(
+            state.board[2][5].as_ref().unwrap().type_,
+            super::PieceType::Man
+        ));
+    }
+
+    #[test]
+    fn frisian_orthogonal_capture() {
+        let mut board = empty_board(10);
+        board[6][3] = p('M', 'W');
+        board[6][5] = p('M', 'B');
+
+        assert_eq!(
+            state(Variant::Frisian, board, 'W').list_valid_moves(),
+            vec![(vec![m(6, 3, 6, 7)], vec![(6, 5)])]
+        );
+    }
+
+    #[test]
+    fn frisian_capture_value() {
+        let mut board = empty_board(10);
+        board[6][1] = p('M', 'W');
+        board[5][2] = p('K', 'B');
+        board[6][7] = p('M', 'W');
+        board[5][8] = p('M', 'B');
+
+        assert_eq!(
+            state(Variant::Frisian, board.clone(), 'W').list_valid_moves(),
+            vec![(vec![m(6, 1, 4, 3)], vec![(5, 2)])]
+        );
+
+        board[3][8] = p('M', 'B');
+        assert_eq!(
+            state(Variant::Frisian, board, 'W').list_valid_moves(),
+            vec![(vec![m(6, 7, 4, 9), m(4, 9, 2, 7)], vec![(5, 8), (3, 8)])]
+        );
+    }
+
+    #[test]
+    fn turkish_setup() {
+        let state = GameState::new(Variant::Turkish);
+
+        assert!(state.board[0].iter().all(Option::is_none));
+        assert!(state.board[1].iter().all(Option::is_some));
+        assert_eq!(state.board.iter().flatten().flatten().count(), 32);
+        assert_eq!(state.list_valid_moves().len(), 8);
+    }
+
+    #[test]
+    fn turkish_removes_captured_pieces_immediately() {
+        let mut board = empty_board(8);
+        board[2][0] = p('K', 'W');
+        board[2][1] = p('M', 'B');
+        board[1][3] = p('M', 'B');
+        board[0][2] = p('M', 'B');
+        board[4][1] = p('M', 'B');
+
+        let moves = state(Variant::Turkish, board, 'W').list_valid_moves();
+
+        assert!(moves
+            .iter()
+            .all(|(_, captures)| captures == &vec![(2, 1), (1, 3), (0, 2), (4, 1)]));
+        assert!(moves
+            .iter()
+            .any(|(moves, _)| moves
+                == &vec![m(2, 0, 2, 3), m(2, 3, 0, 3), m(0, 3, 0, 1), m(0, 1, 5, 1)]));
+    }
+
+    #[test]
+    fn turkish_king_cannot_turn_back() {
+        let mut board = empty_board(8);
+        board[4][3] = p('K', 'W');
+        board[4][4] = p('M', 'B');
+        board[4][1] = p('M', 'B');
+
+        let moves = state(Variant::Turkish, board, 'W').list_valid_moves();
+
+        assert_eq!(moves.len(), 4);
+        assert!(moves.iter().all(|(_, captures)| captures.len() == 1));
+    }
+
+    #[test]
+    fn canadian_setup() {
+        let mut state = GameState::new(Variant::Canadian);
+
+        assert_eq!(state.board.len(), 12);
+        assert_eq!(state.board.iter().flatten().flatten().count(), 60);
+        assert_eq!(state.list_valid_moves().len(), 11);
+
+        state.board = empty_board(12);
+        state.board[11][0] = p('K', 'W');
+        assert_eq!(state.list_valid_moves().len(), 11);
+        assert!(state.apply_sequence(&[m(11, 0, 0, 11)]).is_ok());
+    }
+
+    /// The board of the variant with the given pieces, seen from the side of
+    /// `player`: for black, positions are mirrored through the center.
+    fn promotion_state(
+        variant: Variant,
+        player: char,
+        pieces: &[(usize, usize, char, char)],
+    ) -> GameState {
+        let size = variant.rules().size;
+        let mut board = empty_board(size);
+        for &(x, y, type_, owner) in pieces {
+            let (x, y) = mirror(variant, player, (x, y));
+            board[x][y] = p(type_, owner);
+        }
+
+        state(variant, board, player)
+    }
+
+    fn mirror(variant: Variant, player: char, (x, y): (usize, usize)) -> (usize, usize) {
+        let size = variant.rules().size;
+        if player == 'W' {
+            (x, y)
+        } else {
+            (size - 1 - x, size - 1 - y)
+        }
+    }
+
+    fn mirrored_moves(variant: Variant, player: char, moves: &[Move]) -> Vec<Move> {
+        moves
+            .iter()
+            .map(|m| Move {
+                from: mirror(variant, player, m.from),
+                to: mirror(variant, player, m.to),
+            })
+            .collect()
+    }
+
+    fn is_king(state: &GameState, variant: Variant, player: char, pos: (usize, usize)) -> bool {
+        let (x, y) = mirror(variant, player, pos);
+        matches!(
+            state.board[x][y].as_ref().unwrap().type_,
+            super::PieceType::King
+        )
+    }
+
+    const DIAGONAL_VARIANTS: [Variant; 6] = [
+        Variant::International,
+        Variant::English,
+        Variant::Russian,
+        Variant::Brazilian,
+        Variant::Frisian,
+        Variant::Canadian,
+    ];
+
+    #[test]
+    fn promotion_after_simple_move() {
+        for variant in DIAGONAL_VARIANTS {
+            for (player, opponent) in [('W', 'B'), ('B', 'W')] {
+                let mut state = promotion_state(
+                    variant,
+                    player,
+                    &[(1, 2, 'M', player), (5, 0, 'M', opponent)],
+                );
+
+                state
+                    .apply_sequence(&mirrored_moves(variant, player, &[m(1, 2, 0, 1)]))
+                    .unwrap();
+                assert!(
+                    is_king(&state, variant, player, (0, 1)),
+                    "{variant} {player}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn promotion_after_capture() {
+        for variant in DIAGONAL_VARIANTS {
+            for (player, opponent) in [('W', 'B'), ('B', 'W')] {
+                let mut state = promotion_state(
+                    variant,
+                    player,
+                    &[
+                        (2, 3, 'M', player),
+                        (1, 2, 'M', opponent),
+                        (5, 0, 'M', opponent),
+                    ],
+                );
+
+                state
+                    .apply_sequence(&mirrored_moves(variant, player, &[m(2, 3, 0, 1)]))
+                    .unwrap();
+                assert!(
+                    is_king(&state, variant, player, (0, 1)),
+                    "{variant} {player}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn promotion_while_capturing() {
+        for variant in DIAGONAL_VARIANTS {
+            for (player, opponent) in [('W', 'B'), ('B', 'W')] {
+                let state = promotion_state(
+                    variant,
+                    player,
+                    &[
+                        (2, 1, 'M', player),
+                        (1, 2, 'M', opponent),
+                        (1, 4, 'M', opponent),
+                        (5, 0, 'M', opponent),
+                    ],
+                );
+                let moves = state.list_valid_moves();
+
+                // Where the man ends and whether it is promoted.
+                let (expected, king) = match variant {
+                    // It cannot capture backwards, so its move ends there.
+                    Variant::English => (vec![vec![m(2, 1, 0, 3)]], true),
+                    // It goes on capturing as a flying king.
+                    Variant::Russian => (
+                        [5, 6, 7]
+                            .map(|y| vec![m(2, 1, 0, 3), m(0, 3, y - 3, y)])
+                            .to_vec(),
+                        true,
+                    ),
+                    // It goes on capturing as a man.
+                    _ => (vec![vec![m(2, 1, 0, 3), m(0, 3, 2, 5)]], false),
+                };
+                let expected = expected
+                    .iter()
+                    .map(|moves| mirrored_moves(variant, player, moves))
+                    .collect::<Vec<_>>();
+
+                assert!(
+                    iters_equal_anyorder(moves.iter().map(|m| &m.0), expected.iter()),
+                    "{variant} {player}"
+                );
+                assert_eq!(moves.len(), expected.len(), "{variant} {player}");
+
+                for (sequence, _) in moves {
+                    let mut state = state.clone();
+                    state.apply_sequence(&sequence).unwrap();
+
+                    let to = sequence.last().unwrap().to;
+                    let to = mirror(variant, player, to);
+                    assert_eq!(
+                        is_king(&state, variant, player, to),
+                        king,
+                        "{variant} {player}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn turkish_promotion_while_capturing() {
+        for (player, opponent) in [('W', 'B'), ('B', 'W')] {
+            let variant = Variant::Turkish;
+            let mut state = promotion_state(
+                variant,
+                player,
+                &[
+                    (2, 0, 'M', player),
+                    (1, 0, 'M', opponent),
+                    (0, 1, 'M', opponent),
+                    (5, 5, 'M', opponent),
+                ],
+            );
+
+            // It captures along the last row as a man, and is promoted there.
+            let moves = state.list_valid_moves();
+            let expected = mirrored_moves(variant, player, &[m(2, 0, 0, 0), m(0, 0, 0, 2)]);
+            assert_eq!(moves.len(), 1, "{player}");
+            assert_eq!(moves[0].0, expected, "{player}");
+
+            state.apply_sequence(&expected).unwrap();
+            assert!(is_king(&state, variant, player, (0, 2)), "{player}");
+        }
+    }
+
+    #[test]
+    fn kings_stay_kings() {
+        for variant in DIAGONAL_VARIANTS {
+            let mut state = promotion_state(variant, 'W', &[(0, 1, 'K', 'W'), (5, 0, 'M', 'B')]);
+
+            state.apply_sequence(&[m(0, 1, 1, 2)]).unwrap();
+            assert!(is_king(&state, variant, 'W', (1, 2)), "{variant}");
+        }
+    }
+
+    const VARIANTS: [Variant; 7] = [
+        Variant::International,
+        Variant::English,
+        Variant::Russian,
+        Variant::Brazilian,
+        Variant::Frisian,
+        Variant::Turkish,
+        Variant::Canadian,
+    ];
+
+    #[test]
+    fn position_key_follows_random_games() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for variant in VARIANTS {
+            for _ in 0..10 {
+                let mut state = GameState::new(variant);
+                let initial = state.clone();
+                let mut history = vec![];
+
+                while matches!(state.status, GameStatus::Running) && history.len() < 150 {
+                    let moves = state.list_valid_moves();
+                    let (sequence, _) = moves.choose(&mut rng).unwrap();
+                    let key = state.position_key();
+
+                    history.push((state.apply_sequence(sequence).unwrap(), key));
+                    assert_eq!(state.position_key(), state.compute_key(), "{variant}");
+                    assert_ne!(state.position_key(), key, "{variant}");
+                }
+
+                while let Some((undo, key)) = history.pop() {
+                    state.undo(undo);
+                    assert_eq!(state.position_key(), key, "{variant}");
+                    assert_eq!(state.position_key(), state.compute_key(), "{variant}");
+                }
+
+                assert_eq!(state.to_csv_string(), initial.to_csv_string());
+                assert_eq!(state.current_player, initial.current_player);
+            }
+        }
+    }
+
+    #[test]
+    fn position_key_survives_serialization() {
+        let mut state = GameState::new(Variant::English);
+        let sequence = state.list_valid_moves()[0].0.clone();
+        state.apply_sequence(&sequence).unwrap();
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(!json.contains("key"));
+
+        let restored: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.position_key(), state.position_key());
+    }
+
+    #[test]
+    fn position_key_depends_on_player_and_variant() {
+        let state = GameState::new(Variant::Russian);
+        let brazilian = GameState::from_position(
+            Variant::Brazilian,
+            state.board.clone(),
+            state.current_player,
+            GameStatus::Running,
+        );
+        let black = GameState::from_position(
+            Variant::Russian,
+            state.board.clone(),
+            Player::Black,
+            GameStatus::Running,
+        );
+
+        assert_ne!(state.position_key(), brazilian.position_key());
+        assert_ne!(state.position_key(), black.position_key());
+    }
 }
@@ -0,0 +1,134 @@
+use crate::config::config;
+use rand::{thread_rng, Rng};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    route::{self, Handler},
+    Data, Request, Response, Route,
+};
+use std::{str::FromStr, time::Instant};
+use tracing::{info, info_span, Instrument, Span};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line, with the fields of the enclosing spans.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format: {s}")),
+        }
+    }
+}
+
+/// Installs the global subscriber. Rocket's own logs are forwarded to it as
+/// well, so it must be called before the server is built.
+pub fn init() {
+    let filter = EnvFilter::try_new(&config().log_level).expect("invalid LOG_LEVEL");
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match config().log_format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+    }
+}
+
+/// The span of a request, kept in the request's local cache.
+pub struct RequestSpan {
+    pub id: String,
+    pub span: Span,
+    start: Instant,
+}
+
+impl RequestSpan {
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r Span {
+        &request.local_cache(|| RequestSpan::new(request)).span
+    }
+
+    fn new(request: &Request<'_>) -> Self {
+        let id = format!("{:016x}", thread_rng().gen::<u64>());
+        let span = info_span!(
+            "request",
+            id = %id,
+            method = %request.method(),
+            // The query is left out as it may contain a password.
+            path = %request.uri().path(),
+        );
+
+        Self {
+            id,
+            span,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// Opens a span for each request, logs its outcome and returns its id in the
+/// `X-Request-Id` header.
+pub struct RequestLogger;
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Log requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestSpan::of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_span = request.local_cache(|| RequestSpan::new(request));
+
+        request_span.span.in_scope(|| {
+            info!(
+                status = response.status().code,
+                route = request.route().map(|r| r.uri.path()),
+                duration_ms = request_span.start.elapsed().as_millis() as u64,
+                "response"
+            )
+        });
+        response.set_header(Header::new("X-Request-Id", request_span.id.clone()));
+    }
+}
+
+/// Runs the handler of a route within the span of its request.
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let span = RequestSpan::of(request).clone();
+        self.0.handle(request, data).instrument(span).await
+    }
+}
+
+/// Makes the logs of the handlers of the routes carry the id of their
+/// request.
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
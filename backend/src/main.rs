@@ -1,15 +1,23 @@
-use api::State;
+use api::{contest, jobs, play, SharedState, State};
 use docker::pull_required_images;
-use rocket::fairing::{Fairing, Info, Kind};
+use rocket::fairing::{AdHoc, Fairing, Info, Kind};
 use rocket::http::{ContentType, Header, Method, Status};
-use rocket::launch;
+use rocket::{launch, routes};
 use rocket::{Request, Response};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 pub mod api;
+pub mod auth;
+pub mod book;
+pub mod clock;
 pub mod config;
 pub mod docker;
+pub mod engine;
 pub mod game;
+pub mod logging;
+pub mod metrics;
+pub mod storage;
+pub mod tablebase;
 
 pub struct CORS;
 
@@ -43,11 +51,29 @@ impl Fairing for CORS {
 #[launch]
 fn rocket() -> _ {
     config::config();
+    logging::init();
+    book::book();
 
     pull_required_images();
 
     rocket::build()
         .attach(CORS {})
-        .manage(Mutex::new(State::load().unwrap()))
-        .mount("/", api::routes())
+        .attach(logging::RequestLogger)
+        .attach(metrics::RequestMetrics)
+        .manage(Arc::new(Mutex::new(State::load().unwrap())))
+        .attach(AdHoc::on_liftoff(
+            "Workers, contest scheduler and clocks",
+            |rocket| {
+                Box::pin(async move {
+                    let state = rocket.state::<SharedState>().unwrap().clone();
+                    jobs::start(&state).unwrap();
+                    rocket::tokio::spawn(play::watch_clocks(state.clone()));
+                    rocket::tokio::spawn(contest::schedule(state));
+                    rocket::tokio::task::spawn_blocking(tablebase::init);
+                })
+            },
+        ))
+        .mount("/", logging::traced(api::routes()))
+        .register("/", api::catchers())
+        .mount("/", logging::traced(routes![metrics::metrics]))
 }
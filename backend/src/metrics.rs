@@ -0,0 +1,190 @@
+use crate::{
+    api::{submissions::Language, AIError, AppState, Error},
+    game::GameStatus,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    get,
+    http::ContentType,
+    Data, Request, Response,
+};
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+/// Buckets of the bot duration histogram, in seconds. Compiling a C++ bot
+/// alone takes a few seconds.
+const BOT_DURATION_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    bot_duration: HistogramVec,
+    container_start_failures: IntCounter,
+    ai_errors: IntCounterVec,
+    active_games: IntGauge,
+    queued_jobs: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests handled."),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle HTTP requests.",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let bot_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "bot_duration_seconds",
+                "Time taken by a bot to play a turn.",
+            )
+            .buckets(BOT_DURATION_BUCKETS.to_vec()),
+            &["lang"],
+        )
+        .unwrap();
+        let container_start_failures = IntCounter::new(
+            "container_start_failures_total",
+            "Number of bots that could not be started by Docker.",
+        )
+        .unwrap();
+        let ai_errors = IntCounterVec::new(
+            Opts::new("ai_errors_total", "Number of turns failed by a bot."),
+            &["kind"],
+        )
+        .unwrap();
+        let active_games = IntGauge::new("active_games", "Number of games in progress.").unwrap();
+        let queued_jobs =
+            IntGauge::new("queued_jobs", "Number of jobs waiting for a worker.").unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(bot_duration.clone())).unwrap();
+        registry
+            .register(Box::new(container_start_failures.clone()))
+            .unwrap();
+        registry.register(Box::new(ai_errors.clone())).unwrap();
+        registry.register(Box::new(active_games.clone())).unwrap();
+        registry.register(Box::new(queued_jobs.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            bot_duration,
+            container_start_failures,
+            ai_errors,
+            active_games,
+            queued_jobs,
+        }
+    }
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Records a turn played by a bot.
+pub fn record_bot_run<T>(lang: Language, duration: Duration, result: &Result<T, Error>) {
+    METRICS
+        .bot_duration
+        .with_label_values(&[&lang.to_string()])
+        .observe(duration.as_secs_f64());
+
+    match result {
+        Err(Error::InfrastructureFailure) => METRICS.container_start_failures.inc(),
+        Err(Error::AIFailed { error, .. }) => {
+            let kind = match error {
+                AIError::InvalidMove => "invalidMove",
+                AIError::InvalidOutput => "invalidOutput",
+                AIError::EmptySubmission => "emptySubmission",
+                AIError::Timeout => "timeout",
+            };
+            METRICS.ai_errors.with_label_values(&[kind]).inc();
+        }
+        _ => (),
+    }
+}
+
+/// Counts the requests and their duration per route.
+pub struct RequestMetrics;
+
+/// When the request was received, kept in the request's local cache.
+struct RequestStart(Option<Instant>);
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Record request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // Unmatched requests are grouped together to bound the number of
+        // series.
+        let route = request
+            .route()
+            .map_or("unmatched".to_owned(), |r| r.uri.path().to_owned());
+        let method = request.method().as_str();
+
+        METRICS
+            .requests
+            .with_label_values(&[method, &route, &response.status().code.to_string()])
+            .inc();
+
+        if let RequestStart(Some(start)) = request.local_cache(|| RequestStart(None)) {
+            METRICS
+                .request_duration
+                .with_label_values(&[method, &route])
+                .observe(start.elapsed().as_secs_f64());
+        }
+    }
+}
+
+/// Metrics in the Prometheus text format.
+#[utoipa::path(
+    tag = "metrics",
+    responses((status = 200, description = "Metrics in the Prometheus text format", content_type = "text/plain", body = String)),
+)]
+#[get("/metrics")]
+pub async fn metrics(state: &AppState) -> Result<(ContentType, String), Error> {
+    {
+        let lock = state.lock()?;
+        let active = lock
+            .games
+            .values()
+            .chain(lock.matches.values())
+            .filter(|g| matches!(g.state().status, GameStatus::Running))
+            .count();
+
+        METRICS.active_games.set(active as i64);
+        METRICS.queued_jobs.set(lock.jobs.queued() as i64);
+    }
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|_| Error::IO)?;
+
+    Ok((ContentType::Plain, String::from_utf8(buffer).unwrap()))
+}
@@ -0,0 +1,107 @@
+use super::{Collection, Storage};
+use crate::api::Error;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Fallback storage keeping one JSON file per record, in one directory per
+/// collection.
+#[derive(Debug)]
+pub struct JsonStorage {
+    root: PathBuf,
+    /// Serializes writes so that two records never share a temporary file.
+    write_lock: Mutex<()>,
+}
+
+impl JsonStorage {
+    pub fn open(root: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(root)?;
+
+        Ok(Self {
+            root: root.to_owned(),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn dir(&self, collection: Collection) -> PathBuf {
+        self.root.join(collection.name())
+    }
+
+    /// Keys are user-provided, so they are encoded to be valid file names.
+    fn path(&self, collection: Collection, key: &str) -> PathBuf {
+        self.dir(collection)
+            .join(format!("{}.json", BASE64_URL_SAFE_NO_PAD.encode(key)))
+    }
+
+    /// The records of the collection, sorted by file name.
+    fn paths(&self, collection: Collection) -> Result<Vec<PathBuf>, Error> {
+        let entries = match fs::read_dir(self.dir(collection)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            entries => entries?,
+        };
+
+        let mut paths = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        Ok(paths)
+    }
+}
+
+impl Storage for JsonStorage {
+    fn put(&self, collection: Collection, key: &str, value: &str) -> Result<(), Error> {
+        let _lock = self.write_lock.lock()?;
+
+        fs::create_dir_all(self.dir(collection))?;
+
+        // Written to a temporary file first, so that a crash never leaves a
+        // truncated record behind.
+        let tmp = self.dir(collection).join(".tmp");
+        fs::write(&tmp, value)?;
+        fs::rename(tmp, self.path(collection, key))?;
+
+        Ok(())
+    }
+
+    fn get(&self, collection: Collection, key: &str) -> Result<Option<String>, Error> {
+        match fs::read_to_string(self.path(collection, key)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            value => Ok(Some(value?)),
+        }
+    }
+
+    fn remove(&self, collection: Collection, key: &str) -> Result<(), Error> {
+        match fs::remove_file(self.path(collection, key)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self, collection: Collection) -> Result<Vec<String>, Error> {
+        self.paths(collection)?
+            .into_iter()
+            .map(|p| fs::read_to_string(p).map_err(Error::from))
+            .collect()
+    }
+
+    fn keys(&self, collection: Collection) -> Result<Vec<String>, Error> {
+        let mut keys = self
+            .paths(collection)?
+            .iter()
+            .filter_map(|p| {
+                let key = BASE64_URL_SAFE_NO_PAD.decode(p.file_stem()?.as_encoded_bytes());
+                String::from_utf8(key.ok()?).ok()
+            })
+            .collect::<Vec<_>>();
+        keys.sort();
+
+        Ok(keys)
+    }
+}
@@ -0,0 +1,182 @@
+use crate::api::Error;
+use json::JsonStorage;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlite::SqliteStorage;
+use std::{fmt::Debug, path::Path, str::FromStr, sync::Arc};
+
+pub mod json;
+pub mod sqlite;
+
+/// The kinds of records kept by a `Storage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    Users,
+    Teams,
+    Submissions,
+    /// Every version of every submission, keyed by `<owner>/<version>`.
+    SubmissionHistory,
+    /// Games in progress, along with the games of users not retired yet.
+    Games,
+    /// Games retired with a result, keyed by id.
+    FinishedGames,
+    Contests,
+    /// Holds a single record, `settings`.
+    Settings,
+}
+
+impl Collection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Collection::Users => "users",
+            Collection::Teams => "teams",
+            Collection::Submissions => "submissions",
+            Collection::SubmissionHistory => "submission_history",
+            Collection::Games => "games",
+            Collection::FinishedGames => "finished_games",
+            Collection::Contests => "contests",
+            Collection::Settings => "settings",
+        }
+    }
+}
+
+/// Durable key-value store for the server's state. Values are JSON documents,
+/// use the typed helpers on `dyn Storage` rather than the raw methods.
+pub trait Storage: Send + Sync + Debug {
+    fn put(&self, collection: Collection, key: &str, value: &str) -> Result<(), Error>;
+    fn get(&self, collection: Collection, key: &str) -> Result<Option<String>, Error>;
+    fn remove(&self, collection: Collection, key: &str) -> Result<(), Error>;
+    fn list(&self, collection: Collection) -> Result<Vec<String>, Error>;
+    fn keys(&self, collection: Collection) -> Result<Vec<String>, Error>;
+}
+
+impl dyn Storage + '_ {
+    pub fn save<T: Serialize>(
+        &self,
+        collection: Collection,
+        key: &str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.put(collection, key, &serde_json::to_string(value)?)
+    }
+
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        collection: Collection,
+        key: &str,
+    ) -> Result<Option<T>, Error> {
+        self.get(collection, key)?
+            .map(|value| serde_json::from_str(&value).map_err(Error::from))
+            .transpose()
+    }
+
+    pub fn load_all<T: DeserializeOwned>(&self, collection: Collection) -> Result<Vec<T>, Error> {
+        self.list(collection)?
+            .iter()
+            .map(|value| serde_json::from_str(value).map_err(Error::from))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Sqlite,
+    Json,
+}
+
+impl FromStr for StorageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sqlite" => Ok(Self::Sqlite),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown storage kind: {s}")),
+        }
+    }
+}
+
+/// Opens the storage of the given kind inside `data_dir`.
+pub fn open(kind: StorageKind, data_dir: &Path) -> Result<Arc<dyn Storage>, Error> {
+    Ok(match kind {
+        StorageKind::Sqlite => Arc::new(SqliteStorage::open(&data_dir.join("state.sqlite"))?),
+        StorageKind::Json => Arc::new(JsonStorage::open(&data_dir.join("state"))?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{json::JsonStorage, sqlite::SqliteStorage, Collection, Storage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn roundtrip(storage: &dyn Storage) {
+        storage.save(Collection::Users, "alice", &1).unwrap();
+        storage.save(Collection::Users, "../bob", &2).unwrap();
+        storage.save(Collection::Users, "alice", &3).unwrap();
+        storage.save(Collection::Games, "1", &4).unwrap();
+
+        let mut users = storage.load_all::<i32>(Collection::Users).unwrap();
+        users.sort();
+        assert_eq!(users, vec![2, 3]);
+
+        storage.remove(Collection::Users, "../bob").unwrap();
+        storage.remove(Collection::Users, "missing").unwrap();
+        assert_eq!(storage.load_all::<i32>(Collection::Users).unwrap(), vec![3]);
+        assert_eq!(storage.load_all::<i32>(Collection::Games).unwrap(), vec![4]);
+        assert_eq!(
+            storage.load::<i32>(Collection::Users, "alice").unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            storage.load::<i32>(Collection::Users, "../bob").unwrap(),
+            None
+        );
+        assert_eq!(storage.keys(Collection::Users).unwrap(), vec!["alice"]);
+        assert!(storage
+            .load_all::<i32>(Collection::Submissions)
+            .unwrap()
+            .is_empty());
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "s4s-storage-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn sqlite_roundtrip() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        roundtrip(&SqliteStorage::open(&dir.join("state.sqlite")).unwrap());
+
+        let reopened = SqliteStorage::open(&dir.join("state.sqlite")).unwrap();
+        assert_eq!(
+            (&reopened as &dyn Storage)
+                .load_all::<i32>(Collection::Users)
+                .unwrap(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let dir = temp_dir();
+
+        roundtrip(&JsonStorage::open(&dir).unwrap());
+
+        let reopened = JsonStorage::open(&dir).unwrap();
+        assert_eq!(
+            (&reopened as &dyn Storage)
+                .load_all::<i32>(Collection::Users)
+                .unwrap(),
+            vec![3]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use super::{Collection, Storage};
+use crate::api::Error;
+use rusqlite::{params, Connection};
+use std::{path::Path, sync::Mutex};
+
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let connection = Connection::open(path)?;
+
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS records (
+                collection TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (collection, key)
+            );",
+        )?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn put(&self, collection: Collection, key: &str, value: &str) -> Result<(), Error> {
+        self.connection.lock()?.execute(
+            "INSERT OR REPLACE INTO records (collection, key, value) VALUES (?1, ?2, ?3)",
+            params![collection.name(), key, value],
+        )?;
+
+        Ok(())
+    }
+
+    fn get(&self, collection: Collection, key: &str) -> Result<Option<String>, Error> {
+        let connection = self.connection.lock()?;
+        let mut statement =
+            connection.prepare("SELECT value FROM records WHERE collection = ?1 AND key = ?2")?;
+
+        let value = statement
+            .query_map(params![collection.name(), key], |row| row.get(0))?
+            .next()
+            .transpose()?;
+
+        Ok(value)
+    }
+
+    fn remove(&self, collection: Collection, key: &str) -> Result<(), Error> {
+        self.connection.lock()?.execute(
+            "DELETE FROM records WHERE collection = ?1 AND key = ?2",
+            params![collection.name(), key],
+        )?;
+
+        Ok(())
+    }
+
+    fn list(&self, collection: Collection) -> Result<Vec<String>, Error> {
+        let connection = self.connection.lock()?;
+        let mut statement =
+            connection.prepare("SELECT value FROM records WHERE collection = ?1 ORDER BY key")?;
+
+        let values = statement
+            .query_map(params![collection.name()], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(values)
+    }
+
+    fn keys(&self, collection: Collection) -> Result<Vec<String>, Error> {
+        let connection = self.connection.lock()?;
+        let mut statement =
+            connection.prepare("SELECT key FROM records WHERE collection = ?1 ORDER BY key")?;
+
+        let keys = statement
+            .query_map(params![collection.name()], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(keys)
+    }
+}
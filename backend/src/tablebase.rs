@@ -0,0 +1,926 @@
+use crate::{
+    api::Error,
+    config::config,
+    game::{empty_board, GameState, GameStatus, Piece, PieceType, Player, Position, Variant},
+};
+use rocket::tokio::sync::Notify;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    fs, panic,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Instant,
+};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+/// Tables only cover international draughts, whose board has 50 dark squares.
+const SIZE: usize = 10;
+const SQUARES: usize = SIZE * SIZE / 2;
+
+/// First bytes of the files of the tablebase.
+const MAGIC: &[u8; 4] = b"DRTB";
+/// Version of the format of the files, to increase whenever it or the values
+/// they hold change, so that older files are generated again.
+const VERSION: u8 = 1;
+/// Kinds of files, written in their header.
+const WDL_FILE: u8 = b'W';
+const DTW_FILE: u8 = b'D';
+
+/// Codes of the results of the positions in the WDL files. Impossible
+/// positions are stored as draws.
+const DRAW_CODE: u64 = 0;
+const WIN_CODE: u64 = 1;
+const LOSS_CODE: u64 = 2;
+/// Positions whose result is held by each word of a WDL table.
+const POSITIONS_PER_WORD: usize = 32;
+/// Words between the precomputed ranks of a WDL table.
+const WORDS_PER_BLOCK: usize = 8;
+
+/// Value of the positions which are neither won nor lost. Other values are the
+/// number of sequences of moves left before the end of the game with best
+/// play: odd when the player to move wins, even when they lose.
+const DRAW: u8 = u8::MAX;
+/// Exit of the positions which cannot capture nor promote.
+const NO_EXIT: u8 = u8::MAX - 1;
+/// Longest distance a table can hold.
+const MAX_DISTANCE: u8 = u8::MAX - 2;
+
+fn is_win(value: u8) -> bool {
+    value <= MAX_DISTANCE && value % 2 == 1
+}
+
+fn is_loss(value: u8) -> bool {
+    value <= MAX_DISTANCE && value.is_multiple_of(2)
+}
+
+/// Indices of the groups of pieces of a position.
+const WHITE_MEN: usize = 0;
+const WHITE_KINGS: usize = 1;
+const BLACK_MEN: usize = 2;
+const BLACK_KINGS: usize = 3;
+
+/// Result of a position for the player to move.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Wdl {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// What a tablebase knows of a position.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub struct Probe {
+    pub wdl: Wdl,
+    /// Number of sequences of moves, of both players, left before the game
+    /// ends with best play. `None` for draws.
+    pub distance: Option<u32>,
+}
+
+/// The number of men and kings of each player, in the order of the groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Material([usize; 4]);
+
+impl Display for Material {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let [wm, wk, bm, bk] = self.0;
+        write!(f, "{wm}{wk}{bm}{bk}")
+    }
+}
+
+/// The sorted squares of each group of pieces.
+type Groups = [Vec<u8>; 4];
+
+fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+/// Rank of a sorted combination of squares in colexicographic order.
+fn rank(squares: &[u8]) -> usize {
+    squares
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| binomial(s as usize, i + 1))
+        .sum()
+}
+
+fn unrank(mut rank: usize, k: usize) -> Vec<u8> {
+    let mut squares = vec![0; k];
+    for i in (1..=k).rev() {
+        let mut s = i - 1;
+        while binomial(s + 1, i) <= rank {
+            s += 1;
+        }
+        rank -= binomial(s, i);
+        squares[i - 1] = s as u8;
+    }
+    squares
+}
+
+/// Row and column of a dark square, numbered from the top left.
+fn coordinates(square: u8) -> Position {
+    let row = square as usize / (SIZE / 2);
+    let column = 2 * (square as usize % (SIZE / 2)) + usize::from(row.is_multiple_of(2));
+    (row, column)
+}
+
+fn square_at((row, column): Position) -> u8 {
+    (row * SIZE / 2 + column / 2) as u8
+}
+
+fn group(piece: Piece) -> usize {
+    match (piece.player, piece.type_) {
+        (Player::White, PieceType::Man) => WHITE_MEN,
+        (Player::White, PieceType::King) => WHITE_KINGS,
+        (Player::Black, PieceType::Man) => BLACK_MEN,
+        (Player::Black, PieceType::King) => BLACK_KINGS,
+    }
+}
+
+impl Material {
+    fn of(groups: &Groups) -> Material {
+        Material(groups.clone().map(|g| g.len()))
+    }
+
+    fn pieces(self) -> usize {
+        self.0.iter().sum()
+    }
+
+    fn men(self) -> usize {
+        self.0[WHITE_MEN] + self.0[BLACK_MEN]
+    }
+
+    /// Every material with up to the given number of pieces, each player
+    /// having at least one, in an order where captures and promotions always
+    /// lead to a material which comes before.
+    fn all(pieces: usize) -> Vec<Material> {
+        let mut materials = vec![];
+        for wm in 0..=pieces {
+            for wk in 0..=pieces - wm {
+                for bm in 0..=pieces - wm - wk {
+                    for bk in 0..=pieces - wm - wk - bm {
+                        if wm + wk > 0 && bm + bk > 0 {
+                            materials.push(Material([wm, wk, bm, bk]));
+                        }
+                    }
+                }
+            }
+        }
+
+        materials.sort_by_key(|m| (m.pieces(), m.men()));
+        materials
+    }
+
+    fn path(self, dir: &Path, extension: &str) -> PathBuf {
+        dir.join(format!("{self}.{extension}"))
+    }
+
+    /// Number of entries of the table, including those of impossible
+    /// positions.
+    fn len(self) -> usize {
+        self.0
+            .iter()
+            .map(|&n| binomial(SQUARES, n))
+            .product::<usize>()
+            * 2
+    }
+
+    fn index(self, groups: &Groups, player: Player) -> usize {
+        let index = groups.iter().zip(self.0).fold(0, |acc, (squares, n)| {
+            let mut squares = squares.clone();
+            squares.sort_unstable();
+            acc * binomial(SQUARES, n) + rank(&squares)
+        });
+        index * 2 + usize::from(player == Player::Black)
+    }
+
+    fn position(self, index: usize) -> (Groups, Player) {
+        let player = if index.is_multiple_of(2) {
+            Player::White
+        } else {
+            Player::Black
+        };
+
+        let mut rest = index / 2;
+        let mut groups = Groups::default();
+        for (i, &n) in self.0.iter().enumerate().rev() {
+            let count = binomial(SQUARES, n);
+            groups[i] = unrank(rest % count, n);
+            rest /= count;
+        }
+
+        (groups, player)
+    }
+}
+
+/// Whether no two pieces share a square and no man stands on its last row.
+fn is_valid(groups: &Groups) -> bool {
+    let mut occupied = [false; SQUARES];
+    for square in groups.iter().flatten() {
+        if std::mem::replace(&mut occupied[*square as usize], true) {
+            return false;
+        }
+    }
+
+    groups[WHITE_MEN].iter().all(|&s| coordinates(s).0 != 0)
+        && groups[BLACK_MEN]
+            .iter()
+            .all(|&s| coordinates(s).0 != SIZE - 1)
+}
+
+fn to_state(groups: &Groups, player: Player) -> GameState {
+    let mut board = empty_board(SIZE);
+    for (i, squares) in groups.iter().enumerate() {
+        let piece = Piece {
+            type_: if i.is_multiple_of(2) {
+                PieceType::Man
+            } else {
+                PieceType::King
+            },
+            player: if i < BLACK_MEN {
+                Player::White
+            } else {
+                Player::Black
+            },
+        };
+        for &square in squares {
+            let (row, column) = coordinates(square);
+            board[row][column] = Some(piece.clone());
+        }
+    }
+
+    GameState::from_position(Variant::International, board, player, GameStatus::Running)
+}
+
+/// The groups of the pieces of an international game, or `None` if it has
+/// more pieces than given.
+fn groups_of(state: &GameState, pieces: usize) -> Option<Groups> {
+    if state.variant != Variant::International || state.board.len() != SIZE {
+        return None;
+    }
+
+    let mut groups = Groups::default();
+    let mut count = 0;
+    for (row, line) in state.board.iter().enumerate() {
+        for (column, piece) in line.iter().enumerate() {
+            if let Some(piece) = piece {
+                count += 1;
+                if count > pieces || (row + column).is_multiple_of(2) {
+                    return None;
+                }
+                groups[group(piece.clone())].push(square_at((row, column)));
+            }
+        }
+    }
+
+    Some(groups)
+}
+
+/// Value for the player to move of a position in one of the tables, or `None`
+/// if the table of its material is missing.
+fn lookup(tables: &HashMap<Material, Table>, groups: &Groups, player: Player) -> Option<u8> {
+    let material = Material::of(groups);
+    let own = match player {
+        Player::White => material.0[WHITE_MEN] + material.0[WHITE_KINGS],
+        Player::Black => material.0[BLACK_MEN] + material.0[BLACK_KINGS],
+    };
+    // A player without pieces cannot move, and so has lost.
+    if own == 0 {
+        return Some(0);
+    }
+
+    let table = tables.get(&material)?;
+    Some(table.value(material.index(groups, player)))
+}
+
+/// Value for the player who moved into a position of the given value, or
+/// `None` if its distance is too long for a table.
+fn previous(value: u8) -> Option<u8> {
+    match value {
+        DRAW => Some(DRAW),
+        d if d < MAX_DISTANCE => Some(d + 1),
+        _ => None,
+    }
+}
+
+/// Orders values from the point of view of the player to move.
+fn preference(value: u8) -> (u8, i32) {
+    match value {
+        NO_EXIT => (0, 0),
+        DRAW => (2, 0),
+        d if is_win(d) => (3, -(d as i32)),
+        d => (1, d as i32),
+    }
+}
+
+/// Plays a sequence of moves on the groups of a position, captures and
+/// promotion included.
+fn play(groups: &Groups, from: Position, to: Position, captures: &[Position]) -> Groups {
+    let mut next = groups.clone();
+    let captured = captures.iter().map(|&c| square_at(c)).collect::<Vec<_>>();
+    for squares in next.iter_mut() {
+        squares.retain(|s| !captured.contains(s));
+    }
+
+    let from = square_at(from);
+    let i = next.iter().position(|g| g.contains(&from)).unwrap();
+    next[i].retain(|&s| s != from);
+
+    let promoted = match i {
+        WHITE_MEN => to.0 == 0,
+        BLACK_MEN => to.0 == SIZE - 1,
+        _ => false,
+    };
+    next[if promoted { i + 1 } else { i }].push(square_at(to));
+
+    for squares in next.iter_mut() {
+        squares.sort_unstable();
+    }
+    next
+}
+
+/// Positions of the same material from which the opponent of the player to
+/// move reached this one without capturing nor promoting.
+fn predecessors(material: Material, groups: &Groups, player: Player) -> Vec<usize> {
+    let mut occupied = [[false; SIZE]; SIZE];
+    for &square in groups.iter().flatten() {
+        let (row, column) = coordinates(square);
+        occupied[row][column] = true;
+    }
+    let empty = |row: i32, column: i32| {
+        (0..SIZE as i32).contains(&row)
+            && (0..SIZE as i32).contains(&column)
+            && !occupied[row as usize][column as usize]
+    };
+
+    let opponent = player.opponent();
+    let (men, kings, backwards) = match opponent {
+        Player::White => (WHITE_MEN, WHITE_KINGS, 1),
+        Player::Black => (BLACK_MEN, BLACK_KINGS, -1),
+    };
+
+    let mut result = vec![];
+    let mut push = |i: usize, from: u8, to: Position| {
+        let mut previous = groups.clone();
+        previous[i].retain(|&s| s != from);
+        previous[i].push(square_at(to));
+        result.push(material.index(&previous, opponent));
+    };
+
+    for &square in &groups[men] {
+        let (row, column) = coordinates(square);
+        for dc in [-1, 1] {
+            let (r, c) = (row as i32 + backwards, column as i32 + dc);
+            if empty(r, c) {
+                push(men, square, (r as usize, c as usize));
+            }
+        }
+    }
+
+    for &square in &groups[kings] {
+        let (row, column) = coordinates(square);
+        for (dr, dc) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let (mut r, mut c) = (row as i32 + dr, column as i32 + dc);
+            while empty(r, c) {
+                push(kings, square, (r as usize, c as usize));
+                r += dr;
+                c += dc;
+            }
+        }
+    }
+
+    result
+}
+
+/// Solves every position of a material by retrograde analysis, given the
+/// tables of the materials its captures and promotions lead to. Fails if one
+/// of them is missing, or if a distance is too long for a table.
+fn solve(material: Material, tables: &HashMap<Material, Table>) -> Result<Vec<u8>, Error> {
+    let unsolvable = |reason| {
+        error!(table = %material, reason, "cannot solve an endgame table");
+        Error::IO
+    };
+
+    let len = material.len();
+    let mut values = vec![DRAW; len];
+    // Moves staying in the material whose value is still unknown.
+    let mut remaining = vec![0u8; len];
+    // Best value reachable by capturing or promoting.
+    let mut exits = vec![NO_EXIT; len];
+    let mut must_capture = vec![false; len];
+    let mut propagated = vec![false; len];
+    let mut queues: Vec<Vec<usize>> = vec![vec![]; MAX_DISTANCE as usize + 1];
+
+    for index in 0..len {
+        let (groups, player) = material.position(index);
+        if !is_valid(&groups) {
+            continue;
+        }
+
+        for (moves, captures) in to_state(&groups, player).list_valid_moves() {
+            let (from, to) = (moves[0].from, moves[moves.len() - 1].to);
+            let next = play(&groups, from, to, &captures);
+
+            if captures.is_empty() && Material::of(&next) == material {
+                remaining[index] += 1;
+            } else {
+                must_capture[index] |= !captures.is_empty();
+                let value = lookup(tables, &next, player.opponent())
+                    .ok_or_else(|| unsolvable("missing table"))?;
+                let value = previous(value).ok_or_else(|| unsolvable("distance too long"))?;
+                if preference(value) > preference(exits[index]) {
+                    exits[index] = value;
+                }
+            }
+        }
+
+        if remaining[index] == 0 {
+            match exits[index] {
+                NO_EXIT => values[index] = 0,
+                d if is_loss(d) => values[index] = d,
+                _ => (),
+            }
+        }
+        match values[index] {
+            DRAW => (),
+            d => queues[d as usize].push(index),
+        }
+        if is_win(exits[index]) {
+            queues[exits[index] as usize].push(index);
+        }
+    }
+
+    for distance in 0..=MAX_DISTANCE {
+        let queue = std::mem::take(&mut queues[distance as usize]);
+
+        for index in queue {
+            if values[index] == DRAW {
+                // A win by capturing or promoting, found no quicker way.
+                values[index] = distance;
+            }
+            if values[index] != distance || propagated[index] {
+                continue;
+            }
+            propagated[index] = true;
+
+            let (groups, player) = material.position(index);
+            for previous in predecessors(material, &groups, player) {
+                if values[previous] != DRAW || must_capture[previous] {
+                    continue;
+                }
+
+                if is_loss(distance) {
+                    values[previous] = distance + 1;
+                    queues[distance as usize + 1].push(previous);
+                    continue;
+                }
+
+                remaining[previous] -= 1;
+                if remaining[previous] > 0 {
+                    continue;
+                }
+                let loss = match exits[previous] {
+                    NO_EXIT => distance + 1,
+                    d if is_loss(d) => d.max(distance + 1),
+                    // Drawn, or won by a capture still queued.
+                    _ => continue,
+                };
+                if loss >= MAX_DISTANCE {
+                    return Err(unsolvable("distance too long"));
+                }
+                values[previous] = loss;
+                queues[loss as usize].push(previous);
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// Number of won or lost positions among those of a word of a WDL table.
+fn decided(word: u64) -> usize {
+    ((word | word >> 1) & 0x5555_5555_5555_5555).count_ones() as usize
+}
+
+/// The solved positions of a material. Results take two bits per position,
+/// while only the won and lost positions have a distance.
+#[derive(Debug)]
+struct Table {
+    wdl: Vec<u64>,
+    /// Number of won or lost positions before each block of words.
+    ranks: Vec<usize>,
+    /// Distances of the won and lost positions, in the order of their index.
+    distances: Vec<u8>,
+}
+
+impl Table {
+    fn new(values: &[u8]) -> Table {
+        let mut wdl = vec![0; values.len().div_ceil(POSITIONS_PER_WORD)];
+        let mut distances = vec![];
+        for (index, &value) in values.iter().enumerate() {
+            let code = match value {
+                DRAW => continue,
+                d if is_win(d) => WIN_CODE,
+                _ => LOSS_CODE,
+            };
+            wdl[index / POSITIONS_PER_WORD] |= code << (2 * (index % POSITIONS_PER_WORD));
+            distances.push(value);
+        }
+
+        Table::with(wdl, distances)
+    }
+
+    fn with(wdl: Vec<u64>, distances: Vec<u8>) -> Table {
+        let ranks = wdl
+            .chunks(WORDS_PER_BLOCK)
+            .scan(0, |rank, block| {
+                let start = *rank;
+                *rank += block.iter().map(|&w| decided(w)).sum::<usize>();
+                Some(start)
+            })
+            .collect();
+
+        Table {
+            wdl,
+            ranks,
+            distances,
+        }
+    }
+
+    /// Value of the position for the player to move.
+    fn value(&self, index: usize) -> u8 {
+        let word = index / POSITIONS_PER_WORD;
+        let shift = 2 * (index % POSITIONS_PER_WORD);
+        if (self.wdl[word] >> shift) & 3 == DRAW_CODE {
+            return DRAW;
+        }
+
+        let block = word / WORDS_PER_BLOCK;
+        let rank = self.ranks[block]
+            + self.wdl[block * WORDS_PER_BLOCK..word]
+                .iter()
+                .map(|&w| decided(w))
+                .sum::<usize>()
+            + decided(self.wdl[word] & ((1 << shift) - 1));
+        self.distances[rank]
+    }
+
+    fn header(kind: u8, material: Material, len: usize) -> Vec<u8> {
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        header.push(kind);
+        header.extend(material.0.map(|n| n as u8));
+        header.extend((len as u64).to_le_bytes());
+        header
+    }
+
+    /// Reads the content of a file of the table, if it has the expected
+    /// header.
+    fn read_file(path: &Path, kind: u8, material: Material, len: usize) -> Option<Vec<u8>> {
+        let mut bytes = fs::read(path).ok()?;
+        let header = Table::header(kind, material, len);
+        if !bytes.starts_with(&header) {
+            return None;
+        }
+        bytes.drain(..header.len());
+        Some(bytes)
+    }
+
+    /// Reads the table of the material from `dir`, unless its files are
+    /// missing, of another version or truncated.
+    fn read(dir: &Path, material: Material) -> Option<Table> {
+        let len = material.len();
+        let wdl = Table::read_file(&material.path(dir, "wdl"), WDL_FILE, material, len)?;
+        if wdl.len() != len.div_ceil(POSITIONS_PER_WORD) * 8 {
+            return None;
+        }
+        let wdl = wdl
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        let decided = wdl.iter().map(|&w| decided(w)).sum();
+        let distances = Table::read_file(&material.path(dir, "dtw"), DTW_FILE, material, decided)?;
+        if distances.len() != decided {
+            return None;
+        }
+
+        Some(Table::with(wdl, distances))
+    }
+
+    fn write(&self, dir: &Path, material: Material) -> Result<(), Error> {
+        let mut wdl = Table::header(WDL_FILE, material, material.len());
+        wdl.extend(self.wdl.iter().flat_map(|w| w.to_le_bytes()));
+        let mut distances = Table::header(DTW_FILE, material, self.distances.len());
+        distances.extend(&self.distances);
+
+        fs::write(material.path(dir, "wdl"), wdl)?;
+        fs::write(material.path(dir, "dtw"), distances)?;
+        Ok(())
+    }
+}
+
+/// Results of every position of international draughts with few pieces.
+#[derive(Debug, Default)]
+pub struct Tablebase {
+    pieces: usize,
+    tables: HashMap<Material, Table>,
+}
+
+impl Tablebase {
+    /// Reads the tables of positions with up to `pieces` pieces from `dir`,
+    /// generating and saving the missing ones.
+    pub fn load_or_generate(dir: &Path, pieces: usize) -> Result<Tablebase, Error> {
+        fs::create_dir_all(dir)?;
+
+        let mut tables = HashMap::new();
+        for material in Material::all(pieces) {
+            let table = match Table::read(dir, material) {
+                Some(table) => table,
+                None => {
+                    let start = Instant::now();
+                    let table = Table::new(&solve(material, &tables)?);
+                    table.write(dir, material)?;
+                    // Tables of the first format, which had no header.
+                    let _ = fs::remove_file(material.path(dir, "tb"));
+                    info!(
+                        table = %material,
+                        duration_ms = start.elapsed().as_millis() as u64,
+                        "generated an endgame table"
+                    );
+                    table
+                }
+            };
+            tables.insert(material, table);
+        }
+
+        Ok(Tablebase { pieces, tables })
+    }
+
+    pub fn probe(&self, state: &GameState) -> Option<Probe> {
+        if !matches!(state.status, GameStatus::Running) {
+            return None;
+        }
+
+        let groups = groups_of(state, self.pieces)?;
+        let material = Material::of(&groups);
+        let table = self.tables.get(&material)?;
+
+        Some(
+            match table.value(material.index(&groups, state.current_player)) {
+                DRAW => Probe {
+                    wdl: Wdl::Draw,
+                    distance: None,
+                },
+                d => Probe {
+                    wdl: if is_win(d) { Wdl::Win } else { Wdl::Loss },
+                    distance: Some(d as u32),
+                },
+            },
+        )
+    }
+}
+
+/// The tablebase once `init` is done, `None` if it is disabled or failed to
+/// load.
+static TABLEBASE: OnceLock<Option<Tablebase>> = OnceLock::new();
+static SETTLED: Notify = Notify::const_new();
+
+/// The tablebase, once loaded.
+pub fn tablebase() -> Option<&'static Tablebase> {
+    TABLEBASE.get()?.as_ref()
+}
+
+/// Whether `init` is done, after which probes always give the same answer.
+pub fn is_settled() -> bool {
+    TABLEBASE.get().is_some()
+}
+
+/// Waits until `init` is done.
+pub async fn settled() {
+    loop {
+        // Created before checking, so that it cannot miss the notification.
+        let notified = SETTLED.notified();
+        if is_settled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Loads the tablebase from `DATA_DIR/tablebase`, generating it first if
+/// needed, which takes a while. Probes fail until it is done, and it is
+/// settled without a tablebase if loading fails, even by panicking.
+pub fn init() {
+    let pieces = config().tablebase_pieces;
+    let tablebase = if pieces < 2 {
+        None
+    } else {
+        let dir = PathBuf::from(&config().data_dir).join("tablebase");
+        match panic::catch_unwind(|| Tablebase::load_or_generate(&dir, pieces)) {
+            Ok(Ok(tablebase)) => {
+                info!(pieces, "loaded the endgame tablebase");
+                Some(tablebase)
+            }
+            Ok(Err(err)) => {
+                error!(error = ?err, "failed to load the endgame tablebase");
+                None
+            }
+            Err(_) => {
+                error!("generating the endgame tablebase panicked");
+                None
+            }
+        }
+    };
+
+    let _ = TABLEBASE.set(tablebase);
+    SETTLED.notify_waiters();
+}
+
+impl GameState {
+    /// The result of the position with best play, if it is in the tablebase.
+    pub fn probe(&self) -> Option<Probe> {
+        tablebase()?.probe(self)
+    }
+
+    pub fn probe_wdl(&self) -> Option<Wdl> {
+        self.probe().map(|p| p.wdl)
+    }
+
+    /// Number of sequences of moves left before the game is won or lost with
+    /// best play, if it is in the tablebase and not drawn.
+    pub fn probe_dtw(&self) -> Option<u32> {
+        self.probe()?.distance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        binomial, coordinates, is_valid, lookup, play, preference, previous, rank, solve, to_state,
+        unrank, Material, Probe, Table, Tablebase, Wdl, BLACK_MEN, VERSION, WHITE_MEN,
+    };
+    use crate::game::{GameState, Player};
+    use std::collections::HashMap;
+
+    const KING_KING: Material = Material([0, 1, 0, 1]);
+    const TWO_KINGS_KING: Material = Material([0, 2, 0, 1]);
+
+    fn tables(materials: &[Material]) -> HashMap<Material, Table> {
+        let mut tables = HashMap::new();
+        for &material in materials {
+            let table = Table::new(&solve(material, &tables).unwrap());
+            tables.insert(material, table);
+        }
+        tables
+    }
+
+    /// Checks that the value of every position is the best one among its
+    /// moves.
+    fn check(material: Material, tables: &HashMap<Material, Table>) {
+        for index in 0..material.len() {
+            let (groups, player) = material.position(index);
+            if !is_valid(&groups) {
+                continue;
+            }
+
+            let best = to_state(&groups, player)
+                .list_valid_moves()
+                .into_iter()
+                .map(|(moves, captures)| {
+                    let next = play(&groups, moves[0].from, moves.last().unwrap().to, &captures);
+                    previous(lookup(tables, &next, player.opponent()).unwrap()).unwrap()
+                })
+                .max_by_key(|&v| preference(v))
+                .unwrap_or(0);
+
+            assert_eq!(
+                tables[&material].value(index),
+                best,
+                "{groups:?} {player:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ranks_combinations() {
+        for k in 0..=3 {
+            for r in 0..binomial(50, k) {
+                let squares = unrank(r, k);
+                assert!(squares.windows(2).all(|w| w[0] < w[1]));
+                assert_eq!(rank(&squares), r);
+            }
+        }
+    }
+
+    #[test]
+    fn indexes_positions() {
+        let material = Material([1, 1, 0, 2]);
+        for index in (0..material.len()).step_by(997) {
+            let (groups, player) = material.position(index);
+            assert_eq!(material.index(&groups, player), index);
+        }
+
+        assert_eq!(coordinates(0), (0, 1));
+        assert_eq!(coordinates(5), (1, 0));
+        assert_eq!(coordinates(49), (9, 8));
+    }
+
+    #[test]
+    fn orders_materials_by_dependency() {
+        let materials = Material::all(4);
+
+        assert!(materials.iter().all(|m| m.pieces() <= 4));
+        for (i, material) in materials.iter().enumerate() {
+            // Promoting a man leads to a material solved before.
+            for men in [WHITE_MEN, BLACK_MEN] {
+                if material.0[men] > 0 {
+                    let mut promoted = *material;
+                    promoted.0[men] -= 1;
+                    promoted.0[men + 1] += 1;
+                    assert!(materials[..i].contains(&promoted));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solves_every_position_consistently() {
+        let mut materials = Material::all(2);
+        materials.push(TWO_KINGS_KING);
+        let tables = tables(&materials);
+
+        for material in materials {
+            check(material, &tables);
+        }
+    }
+
+    #[test]
+    #[ignore = "takes a few minutes, run with `cargo test --release -- --ignored`"]
+    fn solves_four_pieces_consistently() {
+        let three_kings_king = Material([0, 3, 0, 1]);
+        let tables = tables(&[KING_KING, TWO_KINGS_KING, three_kings_king]);
+
+        check(three_kings_king, &tables);
+    }
+
+    #[test]
+    fn fails_without_the_tables_it_depends_on() {
+        assert!(solve(KING_KING, &HashMap::new()).is_ok());
+        assert!(solve(TWO_KINGS_KING, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn probes_kings_endings() {
+        let tables = tables(&[KING_KING, TWO_KINGS_KING]);
+        let tablebase = Tablebase { pieces: 3, tables };
+        let state = |white: &[u8], black: u8, player| {
+            to_state(&[vec![], white.to_vec(), vec![], vec![black]], player)
+        };
+
+        // The lone king on the long diagonal cannot be caught.
+        let drawn = tablebase.probe(&state(&[0, 1], 45, Player::White));
+        assert_eq!(drawn.map(|p| p.wdl), Some(Wdl::Draw));
+
+        let won = tablebase.probe(&state(&[22, 49], 27, Player::White));
+        assert_eq!(
+            won,
+            Some(Probe {
+                wdl: Wdl::Win,
+                distance: Some(1),
+            })
+        );
+
+        let mut crowded = state(&[22, 49], 27, Player::White);
+        crowded.board[9][0] = crowded.board[4][5].clone();
+        assert_eq!(tablebase.probe(&crowded), None);
+        assert_eq!(tablebase.probe(&GameState::default()), None);
+    }
+
+    #[test]
+    fn reads_tables_of_the_same_version() {
+        let tables = tables(&[KING_KING, TWO_KINGS_KING]);
+        let table = &tables[&TWO_KINGS_KING];
+        let dir = std::env::temp_dir().join(format!("tablebase-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        table.write(&dir, TWO_KINGS_KING).unwrap();
+
+        let read = Table::read(&dir, TWO_KINGS_KING).unwrap();
+        assert!((0..TWO_KINGS_KING.len()).all(|i| read.value(i) == table.value(i)));
+        assert!(Table::read(&dir, KING_KING).is_none());
+
+        let path = TWO_KINGS_KING.path(&dir, "wdl");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] = VERSION + 1;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Table::read(&dir, TWO_KINGS_KING).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}